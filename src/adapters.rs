@@ -1,69 +1,73 @@
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use crate::adapters::io_worker::{IoOutcome, IoReqOptions, IoThread};
-use crate::id::Id;
+use crate::adapters::io_worker::{IoRequest, IoThread, ResultSlot};
+pub use crate::adapters::io_worker::{io_metrics, set_io_workers};
 
 mod io_worker;
+mod reactor;
 pub mod file;
 pub mod net;
 pub mod fs;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
 
 
-enum SimpleThreadFutureState {
-    NotYetStarted(IoReqOptions),
-    Waiting(Id),
+enum IoFutureState<R: IoRequest> {
+    NotYetStarted(R),
+    Waiting(Arc<ResultSlot<R::Output>>),
     Done
 }
 
-struct SimpleThreadFuture<F>
-{
-    pub state: SimpleThreadFutureState,
-    pub check_outcome: Box<F>
+///sends a request to the io thread, and resolves to whatever it produces
+struct IoFuture<R: IoRequest> {
+    state: IoFutureState<R>,
 }
 
-impl<T, F> SimpleThreadFuture<F>
-where F: Fn(IoOutcome) -> Option<T> {
-    pub fn new (io_req_options: IoReqOptions, check_outcome: F) -> Self {
+impl<R: IoRequest> IoFuture<R> {
+    pub const fn new (request: R) -> Self {
         Self {
-            state: SimpleThreadFutureState::NotYetStarted(io_req_options),
-            check_outcome: Box::new(check_outcome)
+            state: IoFutureState::NotYetStarted(request),
         }
     }
 }
 
+//we never hand out references into the state, so it doesn't matter if it moves
+impl<R: IoRequest> Unpin for IoFuture<R> {}
 
-impl<T, F> Future for SimpleThreadFuture<F>
-where F: Fn(IoOutcome) -> Option<T>
-{
-    type Output = T;
+impl<R: IoRequest> Drop for IoFuture<R> {
+    fn drop(&mut self) {
+        //lets the request know nobody wants its output, in case it has to undo something nobody will see
+        if let IoFutureState::Waiting(slot) = &self.state {
+            slot.abandon();
+        }
+    }
+}
+
+impl<R: IoRequest> Future for IoFuture<R> {
+    type Output = R::Output;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let thread = IoThread::get();
-        match std::mem::replace(&mut self.state, SimpleThreadFutureState::Done) {
-            SimpleThreadFutureState::NotYetStarted(request_options) => {
-                let task_id = thread.send_request(request_options, cx.waker().clone());
-                self.state = SimpleThreadFutureState::Waiting(task_id);
+        match std::mem::replace(&mut self.state, IoFutureState::Done) {
+            IoFutureState::NotYetStarted(request) => {
+                let slot = Arc::new(ResultSlot::new(cx.waker().clone()));
+                IoThread::get().send_request(request, slot.clone());
+                self.state = IoFutureState::Waiting(slot);
                 Poll::Pending
             }
-            SimpleThreadFutureState::Waiting(task_id) => {
-                match thread.try_recv_result(task_id) {
+            IoFutureState::Waiting(slot) => {
+                match slot.take_or_register(cx.waker()) {
                     None => {
-                        self.state = SimpleThreadFutureState::Waiting(task_id);
+                        self.state = IoFutureState::Waiting(slot);
                         Poll::Pending
                     }
-                    Some(res) => {
-                        (self.check_outcome)(res)
-                            .map_or_else(
-                                || panic!("found incorrect type from io thread"),
-                                Poll::Ready
-                            )
-                    }
+                    Some(res) => Poll::Ready(res),
                 }
             }
-            SimpleThreadFutureState::Done => {
+            IoFutureState::Done => {
                 panic!("tried to poll io thread future after completion")
             }
         }
     }
-}
\ No newline at end of file
+}
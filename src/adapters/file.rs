@@ -1,85 +1,134 @@
+use std::future::Future;
+use std::io::SeekFrom;
 use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use crate::id::Id;
-use crate::adapters::io_worker::{IoReqOptions, IoOutcome};
-use crate::adapters::SimpleThreadFuture;
+use crate::adapters::io_worker::IoThread;
+use crate::adapters::io_worker::requests::{
+    CloneFile, CloseFile, CreateFile, FileMetadata, FileToStd, FlushFile, FullyReadFile, LockFile, OpenFile, ReadFile,
+    ReadFileAt, SeekFile, SetFileLen, SyncAll, SyncData, TryLockFile, UnlockFile, WriteAllFile, WriteFile,
+    WriteFileAt,
+};
+#[cfg(unix)]
+use crate::adapters::io_worker::requests::MapFile;
+use crate::adapters::IoFuture;
+use crate::debug;
+use crate::io::{AsyncRead, AsyncWrite};
+
+mod metadata;
+mod open_options;
+#[cfg(unix)]
+mod mmap;
+pub use metadata::Metadata;
+pub use open_options::OpenOptions;
+#[cfg(unix)]
+pub use mmap::Mmap;
+
+#[derive(Default)]
+struct PollState {
+    ///the read [`AsyncRead`] is waiting on, if any. It's kept apart from `writing`, as a read which has been sent
+    ///still moves the file position even if nobody polls it again, so its bytes can't be thrown away.
+    reading: Option<IoFuture<ReadFile>>,
+    ///the last write [`AsyncWrite`] accepted, if it hasn't finished yet. Its bytes were already reported as written,
+    ///so the next write or flush waits for it, and gets any error it hit.
+    writing: Option<IoFuture<WriteAllFile>>,
+    ///bytes which came back from a read after the caller's buffer shrank, waiting to be handed out
+    unread: Vec<u8>,
+}
 
 pub struct File {
     id: Id,
+    poll_state: PollState,
+}
+
+impl std::fmt::Debug for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("File").field("id", &self.id).finish_non_exhaustive()
+    }
 }
 
 impl File {
+    pub(super) fn from_id (id: Id) -> Self {
+        Self {
+            id,
+            poll_state: PollState::default(),
+        }
+    }
+
+    ///takes up to `max_bytes` of anything left over from [`AsyncRead::poll_read`]
+    fn take_unread (&mut self, max_bytes: usize) -> Option<Vec<u8>> {
+        let unread = &mut self.poll_state.unread;
+        if unread.is_empty() {
+            return None;
+        }
+        let n = max_bytes.min(unread.len());
+        Some(unread.drain(..n).collect())
+    }
+
+    ///waits for any read [`AsyncRead::poll_read`] left in flight, so its bytes end up in `unread` rather than lost
+    async fn finish_poll_read (&mut self) -> Result<(), std::io::Error> {
+        if let Some(reading) = self.poll_state.reading.take() {
+            let bytes = reading.await?;
+            self.poll_state.unread.extend(bytes);
+        }
+        Ok(())
+    }
+
+    ///waits for any write [`AsyncWrite::poll_write`] accepted but hasn't finished yet, giving its error if it failed
+    async fn finish_poll_write (&mut self) -> Result<(), std::io::Error> {
+        match self.poll_state.writing.take() {
+            Some(writing) => writing.await,
+            None => Ok(()),
+        }
+    }
+
+    ///polls the write [`AsyncWrite::poll_write`] accepted last, if there's one still going
+    fn poll_finish_write (&mut self, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        let Some(writing) = &mut self.poll_state.writing else {
+            return Poll::Ready(Ok(()));
+        };
+        let res = match Pin::new(writing).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(res) => res,
+        };
+        self.poll_state.writing = None;
+        Poll::Ready(res)
+    }
+
     pub async fn open (p: impl AsRef<Path>) -> Result<Self, std::io::Error> {
-        let id = SimpleThreadFuture::new(
-            IoReqOptions::OpenFile(p.as_ref().to_path_buf()),
-            |outcome| {
-                if let IoOutcome::FileOpenedOrCreated(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await?;
-        Ok(Self {
-            id
-        })
+        let id = IoFuture::new(OpenFile(p.as_ref().to_path_buf())).await?;
+        Ok(Self::from_id(id))
+    }
+
+    ///same as [`OpenOptions::new`]
+    pub fn options () -> OpenOptions {
+        OpenOptions::new()
     }
 
     pub async fn create (path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
-        let id = SimpleThreadFuture::new(
-            IoReqOptions::CreateFile(path.as_ref().to_path_buf()),
-            |outcome| {
-                if let IoOutcome::FileOpenedOrCreated(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await?;
-        Ok(Self {
-            id
-        })
+        let id = IoFuture::new(CreateFile(path.as_ref().to_path_buf())).await?;
+        Ok(Self::from_id(id))
     }
 
-    #[allow(clippy::needless_pass_by_ref_mut)]
     pub async fn read (&mut self, max_bytes: usize) -> Result<Vec<u8>, std::io::Error> {
-        SimpleThreadFuture::new(
-            IoReqOptions::ReadFile(self.id, max_bytes),
-            |outcome| {
-                if let IoOutcome::FileBytesRead(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await
+        self.finish_poll_read().await?;
+        if let Some(unread) = self.take_unread(max_bytes) {
+            return Ok(unread);
+        }
+        IoFuture::new(ReadFile { file: self.id, max_bytes }).await
     }
 
-    #[allow(clippy::needless_pass_by_ref_mut)]
     pub async fn read_to_end (&mut self) -> Result<Vec<u8>, std::io::Error> {
-        SimpleThreadFuture::new(
-            IoReqOptions::FullyReadFile(self.id),
-            |outcome| {
-                if let IoOutcome::FileBytesRead(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await
+        self.finish_poll_read().await?;
+        let mut contents = std::mem::take(&mut self.poll_state.unread);
+        contents.extend(IoFuture::new(FullyReadFile(self.id)).await?);
+        Ok(contents)
     }
 
     #[allow(clippy::needless_pass_by_ref_mut)]
     pub async fn write (&mut self, buf: Vec<u8>) -> Result<usize, std::io::Error> {
-        SimpleThreadFuture::new(
-            IoReqOptions::WriteFile(self.id, buf),
-            |outcome| {
-                if let IoOutcome::FileBytesWritten(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await
+        IoFuture::new(WriteFile(self.id, buf)).await
     }
 
     pub async fn write_all (&mut self, mut buf: &[u8]) -> Result<(), std::io::Error> {
@@ -97,43 +146,449 @@ impl File {
         Ok(())
     }
 
-    pub async fn metadata (&self) -> Result<std::fs::Metadata, std::io::Error> {
-        SimpleThreadFuture::new(
-            IoReqOptions::FileMetadata(self.id),
-            |outcome| {
-                if let IoOutcome::FileMetadata(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await
+    ///moves the file position, returning the new position from the start of the file
+    pub async fn seek (&mut self, mut pos: SeekFrom) -> Result<u64, std::io::Error> {
+        //the io thread's position is past anything we've read but not handed out yet
+        self.finish_poll_read().await?;
+        let unread = std::mem::take(&mut self.poll_state.unread);
+        if let SeekFrom::Current(offset) = &mut pos {
+            *offset -= i64::try_from(unread.len()).unwrap_or(i64::MAX);
+        }
+        IoFuture::new(SeekFile(self.id, pos)).await
     }
 
-    pub async fn into_std(self) -> std::fs::File {
-        SimpleThreadFuture::new(
-            IoReqOptions::FileToStd(self.id),
-            |outcome| {
-                if let IoOutcome::FileToStd(res) = outcome {
-                    Some(res)
-                } else {
-                    None
-                }
-            }
-        ).await
+    pub async fn stream_position (&mut self) -> Result<u64, std::io::Error> {
+        self.seek(SeekFrom::Current(0)).await
+    }
+
+    ///reads up to `max_bytes` from `offset`, leaving the file position where it was
+    pub async fn read_at (&self, offset: u64, max_bytes: usize) -> Result<Vec<u8>, std::io::Error> {
+        IoFuture::new(ReadFileAt { file: self.id, offset, max_bytes }).await
+    }
+
+    ///writes `buf` at `offset`, leaving the file position where it was
+    pub async fn write_at (&self, offset: u64, buf: Vec<u8>) -> Result<usize, std::io::Error> {
+        IoFuture::new(WriteFileAt(self.id, offset, buf)).await
+    }
+
+    pub async fn metadata (&self) -> Result<Metadata, std::io::Error> {
+        IoFuture::new(FileMetadata(self.id)).await
+    }
+
+    pub async fn into_std(mut self) -> Result<std::fs::File, std::io::Error> {
+        self.finish_poll_write().await?;
+        let id = self.id;
+        drop(std::mem::take(&mut self.poll_state));
+        std::mem::forget(self); //the io thread hands the file over rather than closing it
+
+        IoFuture::new(FileToStd(id)).await
     }
     
     #[allow(clippy::needless_pass_by_ref_mut)]
     pub async fn set_len (&mut self, size: u64) -> Result<(), std::io::Error> {
-        SimpleThreadFuture::new(
-            IoReqOptions::SetFileLen(self.id, size),
-            |outcome| {
-                if let IoOutcome::FileLenSet(res) = outcome {
-                    Some(res)
-                } else {
-                    None
+        IoFuture::new(SetFileLen(self.id, size)).await
+    }
+
+    ///waits until everything written, and the file's metadata, has reached the disk
+    pub async fn sync_all (&self) -> Result<(), std::io::Error> {
+        IoFuture::new(SyncAll(self.id)).await
+    }
+
+    ///like [`File::sync_all`], but skips metadata which isn't needed to read the data back, eg. the modified time
+    pub async fn sync_data (&self) -> Result<(), std::io::Error> {
+        IoFuture::new(SyncData(self.id)).await
+    }
+
+    ///flushes anything buffered for the file on the io thread. This doesn't reach the disk - see [`File::sync_all`]
+    ///for that.
+    pub async fn flush (&self) -> Result<(), std::io::Error> {
+        IoFuture::new(FlushFile(self.id)).await
+    }
+
+    ///another handle to the same open file, sharing its position. Anything read into this handle but not handed out
+    ///yet isn't shared.
+    pub async fn try_clone (&self) -> Result<Self, std::io::Error> {
+        let id = IoFuture::new(CloneFile(self.id)).await?;
+        Ok(Self::from_id(id))
+    }
+
+    ///maps the whole file into memory, which can be shared between tasks and saves copying big files through the
+    ///io thread. The mapping is set up, and the kernel asked to start reading it in, on the io thread.
+    ///
+    ///# Safety
+    ///
+    ///The [`Mmap`] is only sound while nothing else - including other processes - changes or shrinks the file
+    ///before it's dropped. Changes show up in a `&[u8]` which is meant to be immutable, and touching pages cut off by
+    ///shrinking the file kills the process with `SIGBUS`.
+    #[cfg(unix)]
+    pub async unsafe fn map_readonly (&self) -> Result<Mmap, std::io::Error> {
+        IoFuture::new(MapFile(self.id)).await
+    }
+
+    ///waits for an exclusive advisory lock on the file (`flock` on Linux), which only stops others taking a lock -
+    ///not reading or writing. Locks belong to the open file, so they're shared with any [`File::try_clone`]s, and
+    ///are released once every handle is closed.
+    ///
+    ///If this is dropped before the lock is taken, it's released again as soon as it is.
+    pub async fn lock_exclusive (&self) -> Result<(), std::io::Error> {
+        IoFuture::new(LockFile { file: self.id, shared: false }).await
+    }
+
+    ///waits for a shared advisory lock on the file, which can be held alongside other shared locks but not an
+    ///exclusive one. Dropping it while it waits is the same as for [`File::lock_exclusive`].
+    pub async fn lock_shared (&self) -> Result<(), std::io::Error> {
+        IoFuture::new(LockFile { file: self.id, shared: true }).await
+    }
+
+    ///takes an exclusive advisory lock without waiting, giving `false` if someone else holds a lock
+    pub async fn try_lock (&self) -> Result<bool, std::io::Error> {
+        IoFuture::new(TryLockFile(self.id)).await
+    }
+
+    pub async fn unlock (&self) -> Result<(), std::io::Error> {
+        IoFuture::new(UnlockFile(self.id)).await
+    }
+
+    ///closes the file, surfacing any errors from flushing or closing it which dropping it would ignore
+    pub async fn close (mut self) -> Result<(), std::io::Error> {
+        self.finish_poll_write().await?;
+        let id = self.id;
+        drop(std::mem::take(&mut self.poll_state));
+        std::mem::forget(self);
+
+        IoFuture::new(CloseFile(id)).await
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, std::io::Error>> {
+        if let Some(unread) = self.take_unread(buf.len()) {
+            buf[..unread.len()].copy_from_slice(&unread);
+            return Poll::Ready(Ok(unread.len()));
+        }
+
+        let id = self.id;
+        let state = &mut self.poll_state;
+        let reading = state.reading.get_or_insert_with(|| IoFuture::new(ReadFile { file: id, max_bytes: buf.len() }));
+
+        let res = match Pin::new(reading).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(res) => res,
+        };
+        state.reading = None;
+
+        Poll::Ready(res.map(|mut bytes| {
+            let n = bytes.len().min(buf.len());
+            buf[..n].copy_from_slice(&bytes[..n]);
+            state.unread = bytes.split_off(n);
+            n
+        }))
+    }
+}
+
+///like tokio's, this takes a copy of each buffer and reports all of it as written straight away, sending it to the
+///io thread to be written in the background. Anything else done to the file queues up behind it, and the next write
+///or flush waits for it to finish and hands back any error.
+impl AsyncWrite for File {
+    fn poll_write (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        if let Err(e) = std::task::ready!(self.poll_finish_write(cx)) {
+            return Poll::Ready(Err(e));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        //polling it sends it to the io thread
+        let mut writing = IoFuture::new(WriteAllFile(self.id, buf.to_vec()));
+        match Pin::new(&mut writing).poll(cx) {
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Ready(Ok(())) => {}
+            Poll::Pending => self.poll_state.writing = Some(writing),
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        //once the last write's done, it's gone straight to the OS so there's nothing else of ours to flush
+        self.poll_finish_write(cx)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        debug!("file", "closing {} on drop", self.id);
+        IoThread::get().send_detached_request(CloseFile(self.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::io::SeekFrom;
+    use std::pin::Pin;
+    use std::task::Poll;
+    use crate::adapters::file::File;
+    use crate::adapters::fs;
+    use crate::adapters::io_worker::BadHandle;
+    use crate::executor::block_on;
+    use crate::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn write_close_and_read_back() {
+        let path = TempPath::new("write_close");
+
+        let contents = block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut file = File::create(&path).await.unwrap();
+                file.write_all(b"hello, world").await.unwrap();
+                file.close().await.unwrap();
+
+                fs::read_to_string(&path).await.unwrap()
+            }
+        });
+
+        assert_eq!(contents, "hello, world");
+    }
+
+    #[test]
+    fn seek_and_read_at() {
+        let path = TempPath::new("seek");
+        std::fs::write(&path, b"0123456789").unwrap();
+
+        block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut file = File::open(&path).await.unwrap();
+                assert_eq!(file.seek(SeekFrom::Start(4)).await.unwrap(), 4);
+                assert_eq!(file.read(3).await.unwrap(), b"456");
+                assert_eq!(file.read_at(1, 2).await.unwrap(), b"12");
+                assert_eq!(file.stream_position().await.unwrap(), 7);
+                assert_eq!(file.seek(SeekFrom::End(-1)).await.unwrap(), 9);
+            }
+        });
+    }
+
+    #[test]
+    fn append_and_create_new() {
+        let path = TempPath::new("open_options");
+
+        block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut file = File::options().write(true).create_new(true).open(&path).await.unwrap();
+                file.write_all(b"abc").await.unwrap();
+                file.close().await.unwrap();
+
+                let err = File::options().write(true).create_new(true).open(&path).await.unwrap_err();
+                assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+                let mut file = File::options().append(true).open(&path).await.unwrap();
+                file.write_all(b"def").await.unwrap();
+            }
+        });
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdef");
+    }
+
+    #[test]
+    fn generic_read_and_write() {
+        async fn copy<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> (from: &mut R, to: &mut W) -> std::io::Result<usize> {
+            let mut contents = vec![];
+            let n = from.read_to_end(&mut contents).await?;
+            to.write_all(&contents).await?;
+            to.flush().await?;
+            Ok(n)
+        }
+
+        let from_path = TempPath::new("generic_from");
+        let to_path = TempPath::new("generic_to");
+        std::fs::write(&from_path, b"0123456789").unwrap();
+
+        block_on({
+            let (from_path, to_path) = (from_path.to_path_buf(), to_path.to_path_buf());
+            async move {
+                let mut from = File::open(&from_path).await.unwrap();
+                let mut header = [0_u8; 2];
+                from.read_exact(&mut header).await.unwrap();
+                assert_eq!(&header, b"01");
+
+                let mut to = File::create(&to_path).await.unwrap();
+                assert_eq!(copy(&mut from, &mut to).await.unwrap(), 8);
+                to.close().await.unwrap();
+            }
+        });
+
+        assert_eq!(std::fs::read(&to_path).unwrap(), b"23456789");
+    }
+
+    #[test]
+    fn write_after_abandoned_read() {
+        let path = TempPath::new("abandoned_read");
+        std::fs::write(&path, b"0123456789").unwrap();
+
+        let read = block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut file = File::options().read(true).write(true).open(&path).await.unwrap();
+
+                //start a read, then give up on it before it's finished
+                let mut buf = [0_u8; 3];
+                let mut read = std::future::poll_fn(|cx| Poll::Ready(match Pin::new(&mut file).poll_read(cx, &mut buf) {
+                    Poll::Ready(n) => buf[..n.unwrap()].to_vec(),
+                    Poll::Pending => vec![],
+                })).await;
+
+                //the io thread still did the read, so this goes after it, and its bytes are still handed out
+                file.write_all(b"ab").await.unwrap();
+                read.extend(file.read_to_end().await.unwrap());
+                read
+            }
+        });
+
+        assert_eq!(read, b"01256789");
+        assert_eq!(std::fs::read(&path).unwrap(), b"012ab56789");
+    }
+
+    #[test]
+    fn write_after_abandoned_write() {
+        let path = TempPath::new("abandoned_write");
+
+        block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut file = File::create(&path).await.unwrap();
+
+                //hand a write over, then never poll it again
+                let accepted = std::future::poll_fn(|cx| Poll::Ready(Pin::new(&mut file).poll_write(cx, b"abc"))).await;
+                assert!(matches!(accepted, Poll::Ready(Ok(3))));
+
+                //which still gets written, ahead of this
+                AsyncWriteExt::write_all(&mut file, b"xyz").await.unwrap();
+                AsyncWriteExt::flush(&mut file).await.unwrap();
+                assert_eq!(fs::read(&path).await.unwrap(), b"abcxyz");
+                file.close().await.unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn use_after_into_std() {
+        let path = TempPath::new("into_std");
+        std::fs::write(&path, b"abc").unwrap();
+
+        block_on({
+            let path = path.to_path_buf();
+            async move {
+                let file = File::open(&path).await.unwrap();
+                //a second handle to the same file, as if it had been copied
+                let mut stale = File::from_id(file.id);
+
+                let _std = file.into_std().await.unwrap();
+
+                let read_err = stale.read(1).await.unwrap_err();
+                assert!(BadHandle::is_bad_handle(&read_err));
+                let metadata_err = stale.metadata().await.unwrap_err();
+                assert!(BadHandle::is_bad_handle(&metadata_err));
+                let into_std_err = stale.into_std().await.unwrap_err();
+                assert!(BadHandle::is_bad_handle(&into_std_err));
+            }
+        });
+    }
+
+    #[test]
+    fn sync_and_clone() {
+        let path = TempPath::new("sync_clone");
+
+        block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut file = File::create(&path).await.unwrap();
+                let mut clone = file.try_clone().await.unwrap();
+
+                file.write_all(b"abc").await.unwrap();
+                //the position is shared, so this carries on after the first write
+                clone.write_all(b"def").await.unwrap();
+                clone.flush().await.unwrap();
+                clone.sync_data().await.unwrap();
+                clone.close().await.unwrap();
+
+                file.sync_all().await.unwrap();
+                assert_eq!(file.stream_position().await.unwrap(), 6);
+            }
+        });
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdef");
+    }
+
+    #[test]
+    fn advisory_locks() {
+        let path = TempPath::new("locks");
+        std::fs::write(&path, b"").unwrap();
+
+        block_on({
+            let path = path.to_path_buf();
+            async move {
+                //locks are per open file, so these conflict even within one process
+                let first = File::open(&path).await.unwrap();
+                let second = File::open(&path).await.unwrap();
+
+                first.lock_exclusive().await.unwrap();
+                assert!(!second.try_lock().await.unwrap());
+                first.unlock().await.unwrap();
+
+                second.lock_shared().await.unwrap();
+                assert!(!first.try_lock().await.unwrap());
+                drop(second);
+                //the drop closes it on the io thread, which releases the lock
+                first.lock_exclusive().await.unwrap();
+
+                //giving up on a wait doesn't leave the lock taken once it comes free
+                let third = File::open(&path).await.unwrap();
+                let mut waiting = Box::pin(third.lock_exclusive());
+                assert!(std::future::poll_fn(|cx| Poll::Ready(waiting.as_mut().poll(cx).is_pending())).await);
+                drop(waiting);
+                first.unlock().await.unwrap();
+                let mut taken = false;
+                for _ in 0..100 {
+                    crate::timer_future::sleep_millis(10).await;
+                    taken = first.try_lock().await.unwrap();
+                    if taken {
+                        break;
+                    }
                 }
+                assert!(taken);
             }
-        ).await
+        });
     }
-}
\ No newline at end of file
+
+    #[cfg(unix)]
+    #[test]
+    fn map_readonly() {
+        let path = TempPath::new("mmap");
+        let contents: Vec<u8> = (0..100_000_u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+
+        let map = block_on({
+            let path = path.to_path_buf();
+            async move {
+                let file = File::open(&path).await.unwrap();
+                //SAFETY: nothing changes the file until we're done with the mapping
+                unsafe { file.map_readonly() }.await.unwrap()
+            }
+        });
+
+        //the mapping outlives the file, and can go to another thread
+        let mapped = std::thread::spawn(move || map.to_vec()).join().unwrap();
+        assert_eq!(mapped, contents);
+
+        std::fs::write(&path, b"").unwrap();
+        let empty = block_on({
+            let path = path.to_path_buf();
+            //SAFETY: as above
+            async move { unsafe { File::open(&path).await.unwrap().map_readonly() }.await.unwrap() }
+        });
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::Permissions;
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+///metadata about a file, from [`File::metadata`](super::File::metadata), [`fs::metadata`](crate::adapters::fs::metadata)
+///and the like. It's like [`std::fs::Metadata`], which can only come from the standard library, so this can also be
+///filled in by `io_uring`.
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    len: u64,
+    kind: Kind,
+    permissions: Permissions,
+    modified: Option<SystemTime>,
+    accessed: Option<SystemTime>,
+    created: Option<SystemTime>,
+}
+
+fn unsupported (what: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, format!("{what} time isn't available here"))
+}
+
+impl Metadata {
+    pub const fn len (&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_file (&self) -> bool {
+        self.kind == Kind::File
+    }
+
+    pub fn is_dir (&self) -> bool {
+        self.kind == Kind::Dir
+    }
+
+    pub fn is_symlink (&self) -> bool {
+        self.kind == Kind::Symlink
+    }
+
+    pub fn permissions (&self) -> Permissions {
+        self.permissions.clone()
+    }
+
+    pub fn modified (&self) -> Result<SystemTime, std::io::Error> {
+        self.modified.ok_or_else(|| unsupported("modified"))
+    }
+
+    pub fn accessed (&self) -> Result<SystemTime, std::io::Error> {
+        self.accessed.ok_or_else(|| unsupported("accessed"))
+    }
+
+    pub fn created (&self) -> Result<SystemTime, std::io::Error> {
+        self.created.ok_or_else(|| unsupported("created"))
+    }
+
+    ///builds it from what a `statx` filled in
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub(crate) fn from_statx (statx: &libc::statx) -> Self {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::Duration;
+
+        let time = |timestamp: libc::statx_timestamp| {
+            let since_epoch = Duration::new(timestamp.tv_sec.unsigned_abs(), timestamp.tv_nsec);
+            if timestamp.tv_sec < 0 {
+                SystemTime::UNIX_EPOCH.checked_sub(since_epoch)
+            } else {
+                SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+            }
+        };
+        //the kernel says which fields it managed to fill in
+        let filled = |field: u32, value| if statx.stx_mask & field == 0 { None } else { value };
+
+        let mode = u32::from(statx.stx_mode);
+        let kind = match mode & libc::S_IFMT {
+            libc::S_IFREG => Kind::File,
+            libc::S_IFDIR => Kind::Dir,
+            libc::S_IFLNK => Kind::Symlink,
+            _ => Kind::Other,
+        };
+
+        Self {
+            len: statx.stx_size,
+            kind,
+            permissions: Permissions::from_mode(mode),
+            modified: filled(libc::STATX_MTIME, time(statx.stx_mtime)),
+            accessed: filled(libc::STATX_ATIME, time(statx.stx_atime)),
+            created: filled(libc::STATX_BTIME, time(statx.stx_btime)),
+        }
+    }
+}
+
+impl From<std::fs::Metadata> for Metadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_file() {
+            Kind::File
+        } else if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_symlink() {
+            Kind::Symlink
+        } else {
+            Kind::Other
+        };
+
+        Self {
+            len: metadata.len(),
+            kind,
+            permissions: metadata.permissions(),
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            created: metadata.created().ok(),
+        }
+    }
+}
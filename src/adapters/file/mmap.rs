@@ -0,0 +1,88 @@
+use std::fmt::{Debug, Formatter};
+use std::fs::File as StdFile;
+use std::ops::Deref;
+use std::os::fd::AsRawFd;
+use std::ptr::NonNull;
+use crate::debug;
+
+///a read-only view of a whole file mapped into memory, from [`File::map_readonly`](super::File::map_readonly).
+///
+///The view sees the file as it is, not as it was when it was mapped - if another process changes the file the
+///bytes can change underneath you, and if it shrinks the file, touching the pages past the new end kills the
+///process with `SIGBUS`. That's why mapping is `unsafe`: only map files which nobody else is going to write to.
+pub struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+//SAFETY: the mapping is read-only and owned by us, so it's like a `Box<[u8]>` which nobody can write to
+unsafe impl Send for Mmap {}
+//SAFETY: as above
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    ///maps all of `file`, and asks the kernel to start reading it in. This blocks, so it belongs on an io worker.
+    ///
+    ///# Safety
+    ///
+    ///Nothing may change or shrink the file while the mapping is alive - see
+    ///[`File::map_readonly`](super::File::map_readonly).
+    pub(crate) unsafe fn map (file: &StdFile) -> Result<Self, std::io::Error> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "file is too big to map"))?;
+        //mmap doesn't allow empty mappings
+        if len == 0 {
+            return Ok(Self { ptr: NonNull::dangling(), len });
+        }
+
+        //SAFETY: just a syscall, and we check the result before using it
+        let ptr = unsafe { libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        //SAFETY: we just mapped `len` bytes at `ptr`. This is only a hint, so it failing doesn't matter.
+        if unsafe { libc::madvise(ptr, len, libc::MADV_WILLNEED) } == -1 {
+            debug!("file", "unable to prefetch mapping: {}", std::io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).expect("mmap succeeded with a null pointer"),
+            len,
+        })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        //SAFETY: `ptr` points to `len` readable bytes until we unmap them on drop (or is dangling with a length of 0)
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Debug for Mmap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mmap").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        //SAFETY: we mapped exactly this, and nothing can still be borrowing it as we're being dropped
+        if unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) } == -1 {
+            debug!("file", "unable to unmap file: {}", std::io::Error::last_os_error());
+        }
+    }
+}
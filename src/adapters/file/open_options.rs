@@ -0,0 +1,69 @@
+use std::fs::OpenOptions as StdOpenOptions;
+use std::path::Path;
+use crate::adapters::file::File;
+use crate::adapters::io_worker::requests::OpenWithOptions;
+use crate::adapters::IoFuture;
+
+///the async version of [`std::fs::OpenOptions`] - set it up the same way, then `open` hands the opening off to the
+///io thread
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    options: StdOpenOptions,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    pub fn new () -> Self {
+        Self {
+            options: StdOpenOptions::new(),
+        }
+    }
+
+    pub fn read (&mut self, read: bool) -> &mut Self {
+        self.options.read(read);
+        self
+    }
+
+    pub fn write (&mut self, write: bool) -> &mut Self {
+        self.options.write(write);
+        self
+    }
+
+    pub fn append (&mut self, append: bool) -> &mut Self {
+        self.options.append(append);
+        self
+    }
+
+    pub fn truncate (&mut self, truncate: bool) -> &mut Self {
+        self.options.truncate(truncate);
+        self
+    }
+
+    pub fn create (&mut self, create: bool) -> &mut Self {
+        self.options.create(create);
+        self
+    }
+
+    ///fails to open if the file already exists
+    pub fn create_new (&mut self, create_new: bool) -> &mut Self {
+        self.options.create_new(create_new);
+        self
+    }
+
+    ///the permission bits a newly created file gets, before the umask
+    #[cfg(unix)]
+    pub fn mode (&mut self, mode: u32) -> &mut Self {
+        std::os::unix::fs::OpenOptionsExt::mode(&mut self.options, mode);
+        self
+    }
+
+    pub async fn open (&self, path: impl AsRef<Path>) -> Result<File, std::io::Error> {
+        let id = IoFuture::new(OpenWithOptions(path.as_ref().to_path_buf(), self.options.clone())).await?;
+        Ok(File::from_id(id))
+    }
+}
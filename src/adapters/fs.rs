@@ -1,11 +1,32 @@
-use std::path::Path;
-use crate::adapters::file::File;
-use crate::adapters::io_worker::{DirChange, IoOutcome, IoReqOptions};
-use crate::adapters::SimpleThreadFuture;
+use std::fs::Permissions;
+use std::path::{Path, PathBuf};
+use crate::adapters::file::Metadata;
+use crate::adapters::io_worker::requests::{
+    Canonicalize, ChangeDir, CopyFile, DirChange, HardLink, PathMetadata, ReadLink, ReadWholeFile, RemoveFile, Rename, SetPermissions,
+    SetTimes, Symlink, TryExists, WriteWholeFile,
+};
+use crate::adapters::IoFuture;
+
+mod atomic;
+mod file_times;
+pub mod glob;
+pub mod read_dir;
+pub mod walk_dir;
+#[cfg(target_os = "linux")]
+pub mod watch;
+pub use atomic::{write_atomic, AtomicFile};
+pub use file_times::FileTimes;
+pub use read_dir::read_dir;
+pub use walk_dir::walk_dir;
+#[cfg(target_os = "linux")]
+pub use watch::{watch, WatchEvent, Watcher};
+
+pub async fn read (p: impl AsRef<Path>) -> Result<Vec<u8>, std::io::Error> {
+    IoFuture::new(ReadWholeFile(p.as_ref().to_path_buf())).await
+}
 
 pub async fn read_to_string (p: impl AsRef<Path>) -> Result<String, std::io::Error> {
-    let mut file = File::open(p).await?;
-    let contents = file.read_to_end().await?;
+    let contents = read(p).await?;
     String::from_utf8(contents).map_err(|utf8_error| {
         std::io::Error::new(
             std::io::ErrorKind::InvalidData,
@@ -14,96 +35,363 @@ pub async fn read_to_string (p: impl AsRef<Path>) -> Result<String, std::io::Err
     })
 }
 
-pub async fn metadata (p: impl AsRef<Path>) -> Result<std::fs::Metadata, std::io::Error> {
-    let file = File::open(p.as_ref()).await?;
-    file.metadata().await
+///the metadata of `p`, or what it points to if it's a symlink
+pub async fn metadata (p: impl AsRef<Path>) -> Result<Metadata, std::io::Error> {
+    IoFuture::new(PathMetadata { path: p.as_ref().to_path_buf(), follow_symlinks: true }).await
+}
+
+///creates the file if needed, and replaces everything in it with `contents`
+pub async fn write (p: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Result<(), std::io::Error> {
+    IoFuture::new(WriteWholeFile(p.as_ref().to_path_buf(), contents.into())).await
+}
+
+///the metadata of `p` itself, rather than what it points to if it's a symlink
+pub async fn symlink_metadata (p: impl AsRef<Path>) -> Result<Metadata, std::io::Error> {
+    IoFuture::new(PathMetadata { path: p.as_ref().to_path_buf(), follow_symlinks: false }).await
+}
+
+pub async fn canonicalize (p: impl AsRef<Path>) -> Result<PathBuf, std::io::Error> {
+    IoFuture::new(Canonicalize(p.as_ref().to_path_buf())).await
+}
+
+pub async fn hard_link (original: impl AsRef<Path>, link: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    IoFuture::new(HardLink(original.as_ref().to_path_buf(), link.as_ref().to_path_buf())).await
+}
+
+///makes `link` a symlink pointing to `original`
+pub async fn symlink (original: impl AsRef<Path>, link: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    IoFuture::new(Symlink(original.as_ref().to_path_buf(), link.as_ref().to_path_buf())).await
+}
+
+pub async fn read_link (p: impl AsRef<Path>) -> Result<PathBuf, std::io::Error> {
+    IoFuture::new(ReadLink(p.as_ref().to_path_buf())).await
+}
+
+pub async fn set_permissions (p: impl AsRef<Path>, permissions: Permissions) -> Result<(), std::io::Error> {
+    IoFuture::new(SetPermissions(p.as_ref().to_path_buf(), permissions)).await
+}
+
+///`Ok(false)` for broken symlinks, and an error if it can't be told either way - eg. without permission
+pub async fn try_exists (p: impl AsRef<Path>) -> Result<bool, std::io::Error> {
+    IoFuture::new(TryExists(p.as_ref().to_path_buf())).await
+}
+
+pub async fn set_times (p: impl AsRef<Path>, times: FileTimes) -> Result<(), std::io::Error> {
+    IoFuture::new(SetTimes(p.as_ref().to_path_buf(), times)).await
 }
 
 pub async fn create_dir (p: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::ChangeDir(p.as_ref().to_path_buf(), DirChange::CreateDir),
-        |outcome| {
-            if let IoOutcome::DirChanged(res) = outcome {
-                Some(res)
-            } else {
-                None
-            }
-        }
-    ).await
+    IoFuture::new(ChangeDir(p.as_ref().to_path_buf(), DirChange::CreateDir)).await
 }
 pub async fn create_dir_all (p: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::ChangeDir(p.as_ref().to_path_buf(), DirChange::CreateDirAll),
-        |outcome| {
-            if let IoOutcome::DirChanged(res) = outcome {
-                Some(res)
-            } else {
-                None
-            }
-        }
-    ).await
+    IoFuture::new(ChangeDir(p.as_ref().to_path_buf(), DirChange::CreateDirAll)).await
 }
 pub async fn remove_dir (p: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::ChangeDir(p.as_ref().to_path_buf(), DirChange::RemoveDir),
-        |outcome| {
-            if let IoOutcome::DirChanged(res) = outcome {
-                Some(res)
-            } else {
-                None
-            }
-        }
-    ).await
+    IoFuture::new(ChangeDir(p.as_ref().to_path_buf(), DirChange::RemoveDir)).await
 }
 pub async fn remove_dir_all (p: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::ChangeDir(p.as_ref().to_path_buf(), DirChange::RemoveDirAll),
-        |outcome| {
-            if let IoOutcome::DirChanged(res) = outcome {
-                Some(res)
-            } else {
-                None
-            }
-        }
-    ).await
+    IoFuture::new(ChangeDir(p.as_ref().to_path_buf(), DirChange::RemoveDirAll)).await
 }
 
 
 pub async fn copy (from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<u64, std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::Copy(from.as_ref().to_path_buf(), to.as_ref().to_path_buf()),
-        |outcome| {
-            if let IoOutcome::Copied(res) = outcome {
-                Some(res)
-            } else {
-                None
-            }
-        }
-    ).await
+    IoFuture::new(CopyFile(from.as_ref().to_path_buf(), to.as_ref().to_path_buf())).await
 }
 
 pub async fn remove_file(p: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::RemoveFile(p.as_ref().to_path_buf()),
-        |outcome| {
-            if let IoOutcome::Removed(res) = outcome {
-                Some(res)
-            } else {
-                None
-            }
-        }
-    ).await
+    IoFuture::new(RemoveFile(p.as_ref().to_path_buf())).await
 }
 
 pub async fn rename (from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    SimpleThreadFuture::new(
-        IoReqOptions::Rename(from.as_ref().to_path_buf(), to.as_ref().to_path_buf()),
-        |outcome| {
-            if let IoOutcome::Renamed(res) = outcome {
-                Some(res)
-            } else {
-                None
+    IoFuture::new(Rename(from.as_ref().to_path_buf(), to.as_ref().to_path_buf())).await
+}
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use crate::adapters::fs;
+    use crate::executor::block_on;
+    use crate::stream::StreamExt;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn list_directory() {
+        let dir = TempPath::new("read_dir");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        //more than one batch's worth
+        for i in 0..100 {
+            std::fs::write(dir.join(format!("file_{i}")), b"").unwrap();
+        }
+
+        let (mut names, n_dirs, len) = block_on({
+            let dir = dir.to_path_buf();
+            async move {
+                let mut entries = fs::read_dir(&dir).await.unwrap();
+                let mut names = vec![];
+                let mut n_dirs = 0;
+                let mut len = None;
+                while let Some(entry) = entries.next().await {
+                    let entry = entry.unwrap();
+                    if entry.file_type().is_dir() {
+                        n_dirs += 1;
+                    }
+                    if entry.file_name() == "file_0" {
+                        len = Some(entry.metadata().await.unwrap().len());
+                    }
+                    names.push(entry.file_name().into_string().unwrap());
+                }
+                (names, n_dirs, len)
+            }
+        });
+
+        names.sort();
+        assert_eq!(names.len(), 101);
+        assert_eq!(names[0], "file_0");
+        assert_eq!(n_dirs, 1);
+        assert_eq!(len, Some(0));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn links_times_and_permissions() {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempPath::new("fs_parity");
+        std::fs::create_dir_all(&dir).unwrap();
+        let fifo = std::ffi::CString::new(dir.join("fifo").as_os_str().as_bytes()).unwrap();
+        //SAFETY: just a syscall, with a nul terminated path which outlives it
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        std::fs::write(dir.join("write_only"), b"").unwrap();
+        std::fs::set_permissions(dir.join("write_only"), std::fs::Permissions::from_mode(0o200)).unwrap();
+
+        block_on({
+            let dir = dir.to_path_buf();
+            async move {
+                let original = dir.join("original");
+                fs::write(&original, "contents").await.unwrap();
+                assert_eq!(fs::read(&original).await.unwrap(), b"contents");
+
+                fs::hard_link(&original, dir.join("hard")).await.unwrap();
+                assert_eq!(fs::read_to_string(dir.join("hard")).await.unwrap(), "contents");
+
+                let link = dir.join("soft");
+                fs::symlink(&original, &link).await.unwrap();
+                assert_eq!(fs::read_link(&link).await.unwrap(), original);
+                assert!(fs::symlink_metadata(&link).await.unwrap().is_symlink());
+                assert_eq!(fs::canonicalize(&link).await.unwrap(), fs::canonicalize(&original).await.unwrap());
+
+                fs::remove_file(&original).await.unwrap();
+                assert!(!fs::try_exists(&link).await.unwrap());
+                assert!(fs::try_exists(dir.join("hard")).await.unwrap());
+
+                let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+                fs::set_times(dir.join("hard"), fs::FileTimes::new().set_modified(modified)).await.unwrap();
+
+                //neither of these can be opened for reading, but their times can still be set
+                let before_epoch = SystemTime::UNIX_EPOCH - Duration::from_millis(1500);
+                fs::set_times(dir.join("fifo"), fs::FileTimes::new().set_modified(before_epoch)).await.unwrap();
+                fs::set_times(dir.join("write_only"), fs::FileTimes::new().set_accessed(modified)).await.unwrap();
+
+                let mut permissions = fs::symlink_metadata(dir.join("hard")).await.unwrap().permissions();
+                permissions.set_readonly(true);
+                fs::set_permissions(dir.join("hard"), permissions).await.unwrap();
             }
+        });
+
+        let metadata = std::fs::metadata(dir.join("hard")).unwrap();
+        assert_eq!(metadata.modified().unwrap(), SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+        assert!(metadata.permissions().readonly());
+        let metadata = std::fs::symlink_metadata(dir.join("fifo")).unwrap();
+        assert_eq!(metadata.modified().unwrap(), SystemTime::UNIX_EPOCH - Duration::from_millis(1500));
+        let metadata = std::fs::metadata(dir.join("write_only")).unwrap();
+        assert_eq!(metadata.accessed().unwrap(), SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+    }
+
+    #[test]
+    fn metadata_of_directories() {
+        let dir = TempPath::new("metadata");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let metadata = block_on({
+            let dir = dir.to_path_buf();
+            async move { fs::metadata(dir).await.unwrap() }
+        });
+
+        assert!(metadata.is_dir());
+        std::fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn watch_changes() {
+        let dir = TempPath::new("watch");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let events = block_on({
+            let dir = dir.to_path_buf();
+            async move {
+                let mut watcher = fs::watch(&dir, true).await.unwrap();
+                let changes = std::thread::spawn({
+                    let dir = dir.clone();
+                    move || {
+                        std::fs::write(dir.join("a"), b"abc").unwrap();
+                        std::fs::rename(dir.join("a"), dir.join("b")).unwrap();
+                        std::fs::remove_file(dir.join("b")).unwrap();
+                    }
+                });
+
+                let mut events = vec![];
+                while let Some(event) = watcher.next().await {
+                    let event = event.unwrap();
+                    let finished = matches!(event, fs::WatchEvent::Removed(_));
+                    events.push(event);
+                    if finished {
+                        break;
+                    }
+                }
+                changes.join().unwrap();
+                events
+            }
+        });
+
+        assert_eq!(events, [
+            fs::WatchEvent::Created(dir.join("a")),
+            fs::WatchEvent::Modified(dir.join("a")),
+            fs::WatchEvent::Renamed { from: dir.join("a"), to: dir.join("b") },
+            fs::WatchEvent::Removed(dir.join("b")),
+        ]);
+    }
+
+    #[test]
+    fn watch_directories_moving() {
+        let dir = TempPath::new("watch_moves");
+        let outside = TempPath::new("watch_moves_outside");
+        std::fs::create_dir_all(dir.join("root")).unwrap();
+        let root = dir.join("root");
+
+        let events = block_on({
+            let (root, outside) = (root.clone(), outside.to_path_buf());
+            async move {
+                let mut watcher = fs::watch(&root, true).await.unwrap();
+                let mut next = async || watcher.next().await.map(Result::unwrap);
+
+                std::fs::create_dir(root.join("a")).unwrap();
+                let mut events = vec![next().await];
+                //which is watched by the time we hear about it
+                std::fs::write(root.join("a/x"), b"").unwrap();
+                events.push(next().await);
+
+                std::fs::rename(root.join("a"), root.join("b")).unwrap();
+                events.push(next().await);
+                std::fs::write(root.join("b/y"), b"").unwrap();
+                events.push(next().await);
+
+                //nothing more from it once it's gone
+                std::fs::rename(root.join("b"), &outside).unwrap();
+                events.push(next().await);
+                std::fs::write(outside.join("z"), b"").unwrap();
+
+                //only the once, even though it was being watched itself as well
+                std::fs::create_dir(root.join("c")).unwrap();
+                events.push(next().await);
+                std::fs::remove_dir(root.join("c")).unwrap();
+                events.push(next().await);
+
+                std::fs::remove_dir(&root).unwrap();
+                while let Some(event) = next().await {
+                    events.push(Some(event));
+                }
+                events
+            }
+        });
+
+        assert_eq!(events, [
+            Some(fs::WatchEvent::Created(root.join("a"))),
+            Some(fs::WatchEvent::Created(root.join("a/x"))),
+            Some(fs::WatchEvent::Renamed { from: root.join("a"), to: root.join("b") }),
+            Some(fs::WatchEvent::Created(root.join("b/y"))),
+            Some(fs::WatchEvent::Removed(root.join("b"))),
+            Some(fs::WatchEvent::Created(root.join("c"))),
+            Some(fs::WatchEvent::Removed(root.join("c"))),
+            Some(fs::WatchEvent::Removed(root)),
+        ]);
+    }
+
+    #[test]
+    fn atomic_writes() {
+        let dir = TempPath::new("atomic");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        std::fs::write(&path, b"old").unwrap();
+        #[cfg(unix)]
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o600)).unwrap();
+
+        block_on({
+            let path = path.clone();
+            async move {
+                let mut abandoned = fs::AtomicFile::create(&path).await.unwrap();
+                abandoned.write_all(b"half written").await.unwrap();
+                drop(abandoned);
+                assert_eq!(fs::read(&path).await.unwrap(), b"old");
+
+                fs::write_atomic(&path, "new").await.unwrap();
+            }
+        });
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&path).unwrap().permissions()) & 0o777, 0o600);
+        //wait for the abandoned temporary file's detached removal
+        for _ in 0..100 {
+            if std::fs::read_dir(&dir).unwrap().count() == 1 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
-    ).await
-}
\ No newline at end of file
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn walk_tree() {
+        let root = TempPath::new("walk_dir");
+        for dir in ["a/b/c", "a/target", "d"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["a/one.rs", "a/b/two.rs", "a/b/c/three.rs", "a/target/built.rs", "d/notes.txt"] {
+            std::fs::write(root.join(file), b"").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&root, root.join("d/loop")).unwrap();
+
+        let walk = |walk: fs::walk_dir::WalkDir| block_on(async move {
+            let mut walk = walk;
+            let (mut found, mut errors) = (vec![], 0);
+            while let Some(entry) = walk.next().await {
+                match entry {
+                    Ok(entry) => found.push(entry.path().to_string_lossy().replace('\\', "/")),
+                    Err(_) => errors += 1,
+                }
+            }
+            (found, errors)
+        });
+        let relative = |found: Vec<String>| -> Vec<String> {
+            let prefix = format!("{}/", root.to_string_lossy().replace('\\', "/"));
+            found.into_iter().map(|path| path.trim_start_matches(&prefix).to_string()).collect()
+        };
+
+        let (found, errors) = walk(fs::walk_dir(&root).sorted(true).include("*.rs").exclude("target"));
+        assert_eq!(relative(found), ["a/b/c/three.rs", "a/b/two.rs", "a/one.rs"]);
+        assert_eq!(errors, 0);
+
+        let (found, _) = walk(fs::walk_dir(&root).sorted(true).max_depth(2).include("a/*"));
+        assert_eq!(relative(found), ["a/b", "a/one.rs", "a/target"]);
+
+        //following the symlink back to the root is a loop, which is reported rather than walked forever
+        #[cfg(unix)]
+        {
+            let (found, errors) = walk(fs::walk_dir(&root).follow_links(true).include("three.rs"));
+            assert_eq!(relative(found), ["a/b/c/three.rs"]);
+            assert_eq!(errors, 1);
+        }
+    }
+}
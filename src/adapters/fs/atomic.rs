@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use crate::adapters::file::File;
+use crate::adapters::fs;
+use crate::adapters::io_worker::IoThread;
+use crate::adapters::io_worker::requests::{CopyPermissions, RemoveFile};
+use crate::adapters::IoFuture;
+use crate::io::AsyncWrite;
+use crate::debug;
+
+///a file which is written somewhere temporary, and only replaces `path` once it's [committed](Self::commit) - so
+///anyone reading `path` sees either the old contents or all of the new ones, even if we crash partway through.
+///
+///If `path` already exists, the new file takes on its permissions, and its owner where we're allowed to change that.
+///If it's dropped without being committed, the temporary file is removed and `path` is left alone.
+pub struct AtomicFile {
+    ///`None` once it's been closed by `commit`
+    file: Option<File>,
+    path: PathBuf,
+    temp_path: PathBuf,
+    committed: bool,
+}
+
+///a name next to `path` which nothing else should be using - it has to be in the same directory, as renames can't
+///cross filesystems
+fn temp_path_for (path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+impl AtomicFile {
+    pub async fn create (path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_path_buf();
+        let temp_path = temp_path_for(&path);
+        let file = File::options().write(true).create_new(true).open(&temp_path).await?;
+
+        Ok(Self {
+            file: Some(file),
+            path,
+            temp_path,
+            committed: false,
+        })
+    }
+
+    pub fn path (&self) -> &Path {
+        &self.path
+    }
+
+    const fn file (&mut self) -> &mut File {
+        self.file.as_mut().expect("AtomicFile used after commit")
+    }
+
+    pub async fn write_all (&mut self, buf: &[u8]) -> Result<(), std::io::Error> {
+        self.file().write_all(buf).await
+    }
+
+    ///syncs the new contents to disk, then moves them over `path`
+    pub async fn commit (mut self) -> Result<(), std::io::Error> {
+        let file = self.file.take().expect("AtomicFile committed twice");
+
+        //otherwise what replaces `path` would have the default permissions for a new file
+        IoFuture::new(CopyPermissions { from: self.path.clone(), to: self.temp_path.clone() }).await?;
+        file.sync_all().await?;
+        file.close().await?;
+        fs::rename(&self.temp_path, &self.path).await?;
+        self.committed = true;
+
+        //the rename only survives a crash once the directory it happened in has been synced too
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent() {
+            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+            File::open(dir).await?.sync_all().await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl AsyncWrite for AtomicFile {
+    fn poll_write (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(self.file()).poll_write(cx, buf)
+    }
+
+    fn poll_flush (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(self.file()).poll_flush(cx)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            debug!("fs", "removing uncommitted {}", self.temp_path.display());
+            //closes the file first, although unix is happy to remove it while it's open anyway
+            drop(self.file.take());
+            IoThread::get().send_detached_request(RemoveFile(self.temp_path.clone()));
+        }
+    }
+}
+
+///replaces the contents of `path` with `contents`, so that anyone reading it sees either all of the old contents or
+///all of the new ones. See [`AtomicFile`].
+pub async fn write_atomic (path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<(), std::io::Error> {
+    let mut file = AtomicFile::create(path).await?;
+    file.write_all(contents.as_ref()).await?;
+    file.commit().await
+}
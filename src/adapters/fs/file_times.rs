@@ -0,0 +1,75 @@
+use std::time::SystemTime;
+
+///the times to give a file in [`set_times`](super::set_times) - anything left unset stays as it is. It's built the
+///same way as [`std::fs::FileTimes`], which can't be read back, so it couldn't be set by path.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileTimes {
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+}
+
+impl FileTimes {
+    pub const fn new () -> Self {
+        Self {
+            accessed: None,
+            modified: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn set_accessed (mut self, t: SystemTime) -> Self {
+        self.accessed = Some(t);
+        self
+    }
+
+    #[must_use]
+    pub const fn set_modified (mut self, t: SystemTime) -> Self {
+        self.modified = Some(t);
+        self
+    }
+
+    ///the accessed and modified times, in the form `utimensat` wants them
+    #[cfg(unix)]
+    pub(crate) fn to_timespecs (self) -> Result<[libc::timespec; 2], std::io::Error> {
+        Ok([to_timespec(self.accessed)?, to_timespec(self.modified)?])
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn to_std (self) -> std::fs::FileTimes {
+        let mut times = std::fs::FileTimes::new();
+        if let Some(accessed) = self.accessed {
+            times = times.set_accessed(accessed);
+        }
+        if let Some(modified) = self.modified {
+            times = times.set_modified(modified);
+        }
+        times
+    }
+}
+
+#[cfg(unix)]
+fn to_timespec (time: Option<SystemTime>) -> Result<libc::timespec, std::io::Error> {
+    let Some(time) = time else {
+        return Ok(libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_OMIT });
+    };
+
+    let out_of_range = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "time is out of range for this platform");
+    let (secs, nanos) = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => (libc::time_t::try_from(since.as_secs()).map_err(|_| out_of_range())?, since.subsec_nanos()),
+        Err(e) => {
+            //timespecs before the epoch still have positive nanoseconds, counting up from the second before
+            let before = e.duration();
+            let secs = libc::time_t::try_from(before.as_secs()).map_err(|_| out_of_range())?;
+            match before.subsec_nanos() {
+                0 => (-secs, 0),
+                nanos => (-secs - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+
+    Ok(libc::timespec {
+        tv_sec: secs,
+        #[allow(clippy::cast_lossless, clippy::cast_possible_wrap)]
+        tv_nsec: nanos as libc::c_long,
+    })
+}
@@ -0,0 +1,158 @@
+use std::path::Path;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Literal(char),
+    ///`?`
+    AnyChar,
+    ///`*`, which doesn't cross a `/`
+    Star,
+    ///`**`, which does
+    DoubleStar,
+    ///`[a-z_]`, or `[!a-z_]` if negated
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+///a shell-style pattern for [`walk_dir`](crate::adapters::fs::walk_dir) filters. Patterns without a `/` match
+///against the file name, and ones with a `/` match against the whole path relative to the root.
+///
+///Anything which doesn't parse as a pattern (eg. an unclosed `[`) is matched literally.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    whole_path: bool,
+}
+
+impl Glob {
+    pub fn new (pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = vec![];
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    tokens.push(Token::DoubleStar);
+                    i += 2;
+                    //`**/` also matches no directories at all
+                    if chars.get(i) == Some(&'/') {
+                        i += 1;
+                    }
+                    continue;
+                }
+                '*' => tokens.push(Token::Star),
+                '?' => tokens.push(Token::AnyChar),
+                '[' => {
+                    if let Some((class, len)) = parse_class(&chars[i..]) {
+                        tokens.push(class);
+                        i += len;
+                        continue;
+                    }
+                    tokens.push(Token::Literal('['));
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+            i += 1;
+        }
+
+        Self {
+            tokens,
+            whole_path: pattern.contains('/'),
+        }
+    }
+
+    ///`relative` is relative to the root of the walk
+    pub fn matches (&self, relative: &Path) -> bool {
+        let text: String = if self.whole_path {
+            relative.components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        } else {
+            relative.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+        };
+
+        let text: Vec<char> = text.chars().collect();
+        matches_from(&self.tokens, &text)
+    }
+}
+
+///parses a `[...]` class from the start of `chars`, giving the token and how many chars it took up
+fn parse_class (chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 1;
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = vec![];
+    //a `]` straight away is part of the class rather than closing it
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        first = false;
+
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                ranges.push((c, end));
+                i += 3;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+fn matches_from (tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        Token::Literal(c) => text.first() == Some(c) && matches_from(rest, &text[1..]),
+        Token::AnyChar => text.first().is_some_and(|&c| c != '/') && matches_from(rest, &text[1..]),
+        Token::Class { negated, ranges } => text.first().is_some_and(|&c| {
+            c != '/' && ranges.iter().any(|&(start, end)| (start..=end).contains(&c)) != *negated
+        }) && matches_from(rest, &text[1..]),
+        Token::Star => {
+            //try every length which doesn't go past a `/`
+            let max = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=max).any(|n| matches_from(rest, &text[n..]))
+        }
+        Token::DoubleStar => (0..=text.len())
+            //only start matching the rest at the beginning of a component, so `**/b` doesn't match `ab`
+            .filter(|&n| n == 0 || n == text.len() || text[n - 1] == '/' || rest.is_empty())
+            .any(|n| matches_from(rest, &text[n..])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::adapters::fs::glob::Glob;
+
+    #[test]
+    fn glob_matching() {
+        let matches = |pattern: &str, path: &str| Glob::new(pattern).matches(Path::new(path));
+
+        assert!(matches("*.rs", "src/main.rs"));
+        assert!(!matches("*.rs", "src/main.rsx"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/adapters/fs.rs"));
+        assert!(matches("src/**/*.rs", "src/adapters/fs.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("**/target", "target"));
+        assert!(!matches("**/get", "target"));
+        assert!(matches("file_[0-4]?", "file_37"));
+        assert!(!matches("file_[!0-4]", "file_3"));
+        assert!(matches("weird[", "weird["));
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fmt::{Debug, Formatter};
+use std::fs::FileType;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use crate::adapters::file::Metadata;
+use crate::adapters::io_worker::IoThread;
+use crate::adapters::io_worker::requests::{CloseDir, DirEntryMetadata, OpenDir, ReadDirBatch};
+use crate::adapters::IoFuture;
+use crate::id::Id;
+use crate::stream::Stream;
+use crate::debug;
+
+///how many entries to ask the io thread for at once
+const BATCH_SIZE: usize = 64;
+
+///one entry from [`read_dir`]. The name, path and file type come with it, but the metadata needs another trip to the
+///io thread.
+#[derive(Clone)]
+pub struct DirEntry {
+    inner: Arc<std::fs::DirEntry>,
+    file_type: FileType,
+}
+
+impl DirEntry {
+    pub fn file_name (&self) -> OsString {
+        self.inner.file_name()
+    }
+
+    pub fn path (&self) -> PathBuf {
+        self.inner.path()
+    }
+
+    pub const fn file_type (&self) -> FileType {
+        self.file_type
+    }
+
+    ///the entry's metadata, without following symlinks
+    pub async fn metadata (&self) -> Result<Metadata, std::io::Error> {
+        IoFuture::new(DirEntryMetadata(self.inner.clone())).await
+    }
+}
+
+impl Debug for DirEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DirEntry").field(&self.path()).finish()
+    }
+}
+
+///the entries of a directory, from [`read_dir`]. They come in whatever order the OS gives them, and don't include
+///`.` or `..`.
+#[must_use = "streams do nothing unless polled"]
+pub struct ReadDir {
+    id: Id,
+    batch: VecDeque<Result<DirEntry, std::io::Error>>,
+    in_flight: Option<IoFuture<ReadDirBatch>>,
+    finished: bool,
+}
+
+pub async fn read_dir (p: impl AsRef<Path>) -> Result<ReadDir, std::io::Error> {
+    let id = IoFuture::new(OpenDir(p.as_ref().to_path_buf())).await?;
+    Ok(ReadDir {
+        id,
+        batch: VecDeque::new(),
+        in_flight: None,
+        finished: false,
+    })
+}
+
+impl Stream for ReadDir {
+    type Item = Result<DirEntry, std::io::Error>;
+
+    fn poll_next (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(entry) = self.batch.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+            if self.finished {
+                return Poll::Ready(None);
+            }
+
+            let id = self.id;
+            let in_flight = self.in_flight.get_or_insert_with(|| IoFuture::new(ReadDirBatch {
+                dir: id,
+                max_entries: BATCH_SIZE,
+            }));
+            let res = match Pin::new(in_flight).poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(res) => res,
+            };
+            self.in_flight = None;
+
+            match res {
+                Ok(batch) if batch.is_empty() => self.finished = true,
+                Ok(batch) => self.batch.extend(batch.into_iter().map(|entry| entry.map(|(inner, file_type)| DirEntry {
+                    inner: Arc::new(inner),
+                    file_type,
+                }))),
+                Err(e) => {
+                    //the listing itself is broken, so there's no point asking for more
+                    self.finished = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        debug!("fs", "closing directory listing {}", self.id);
+        IoThread::get().send_detached_request(CloseDir(self.id));
+    }
+}
@@ -0,0 +1,314 @@
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs::FileType;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::collections::VecDeque;
+use crate::adapters::fs::glob::Glob;
+use crate::adapters::io_worker::requests::WalkBatch;
+use crate::adapters::IoFuture;
+use crate::stream::Stream;
+
+///how many entries to ask the io thread for at once
+const BATCH_SIZE: usize = 128;
+
+///one entry found by [`walk_dir`]
+#[derive(Clone, Debug)]
+pub struct WalkEntry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+}
+
+impl WalkEntry {
+    pub fn path (&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file_name (&self) -> OsString {
+        self.path.file_name().map(ToOwned::to_owned).unwrap_or_default()
+    }
+
+    ///how far below the root this is - the root's own entries are at depth 1
+    pub const fn depth (&self) -> usize {
+        self.depth
+    }
+
+    ///the type of what the entry points to if following symlinks, otherwise of the entry itself
+    pub const fn file_type (&self) -> FileType {
+        self.file_type
+    }
+}
+
+///something which went wrong partway through a walk - the rest of the walk carries on regardless
+#[derive(Debug)]
+pub struct WalkError {
+    path: PathBuf,
+    error: std::io::Error,
+}
+
+impl WalkError {
+    ///the path which couldn't be read
+    pub fn path (&self) -> &Path {
+        &self.path
+    }
+
+    pub const fn io_error (&self) -> &std::io::Error {
+        &self.error
+    }
+}
+
+impl Display for WalkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for WalkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<WalkError> for std::io::Error {
+    fn from(value: WalkError) -> Self {
+        Self::new(value.error.kind(), value)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct WalkOptions {
+    max_depth: Option<usize>,
+    follow_links: bool,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    sorted: bool,
+}
+
+type Entries = Box<dyn Iterator<Item = Result<std::fs::DirEntry, std::io::Error>> + Send>;
+
+///a directory which is being read
+struct Level {
+    path: PathBuf,
+    depth: usize,
+    entries: Entries,
+    ///where the directory really is, for spotting loops when following symlinks
+    canonical: Option<PathBuf>,
+}
+
+///the state of a walk, which lives on an io worker while it's finding the next batch
+pub struct Walker {
+    root: PathBuf,
+    options: WalkOptions,
+    started: bool,
+    stack: Vec<Level>,
+    ///an error to report after the entry which caused it
+    deferred_error: Option<WalkError>,
+}
+
+impl Walker {
+    fn open_dir (&self, path: &Path, depth: usize) -> Result<Level, WalkError> {
+        let walk_error = |error| WalkError { path: path.to_path_buf(), error };
+
+        let canonical = if self.options.follow_links {
+            let canonical = std::fs::canonicalize(path).map_err(walk_error)?;
+            if self.stack.iter().any(|level| level.canonical.as_ref() == Some(&canonical)) {
+                return Err(walk_error(std::io::Error::other(format!(
+                    "filesystem loop: {} is an ancestor of itself",
+                    canonical.display()
+                ))));
+            }
+            Some(canonical)
+        } else {
+            None
+        };
+
+        let read_dir = std::fs::read_dir(path).map_err(walk_error)?;
+        let entries: Entries = if self.options.sorted {
+            let mut entries: Vec<_> = read_dir.collect();
+            //errors don't have a name, so they go last
+            entries.sort_by(|a, b| match (a, b) {
+                (Ok(a), Ok(b)) => a.file_name().cmp(&b.file_name()),
+                (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+                (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+                (Err(_), Err(_)) => std::cmp::Ordering::Equal,
+            });
+            Box::new(entries.into_iter())
+        } else {
+            Box::new(read_dir)
+        };
+
+        Ok(Level {
+            path: path.to_path_buf(),
+            depth,
+            entries,
+            canonical,
+        })
+    }
+
+    fn relative<'a> (&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    pub(crate) fn next_entry (&mut self) -> Option<Result<WalkEntry, WalkError>> {
+        if let Some(error) = self.deferred_error.take() {
+            return Some(Err(error));
+        }
+
+        if !self.started {
+            self.started = true;
+            match self.open_dir(&self.root, 0) {
+                Ok(level) => self.stack.push(level),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        loop {
+            let level = self.stack.last_mut()?;
+            let depth = level.depth + 1;
+            let entry = match level.entries.next() {
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+                Some(Err(error)) => return Some(Err(WalkError { path: level.path.clone(), error })),
+                Some(Ok(entry)) => entry,
+            };
+
+            let path = entry.path();
+            let relative = self.relative(&path);
+            if self.options.exclude.iter().any(|glob| glob.matches(relative)) {
+                continue;
+            }
+
+            let file_type = match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() && self.options.follow_links => {
+                    std::fs::metadata(&path).map(|metadata| metadata.file_type())
+                }
+                res => res,
+            };
+            let file_type = match file_type {
+                Ok(file_type) => file_type,
+                Err(error) => return Some(Err(WalkError { path, error })),
+            };
+
+            if file_type.is_dir() && self.options.max_depth.is_none_or(|max| depth < max) {
+                match self.open_dir(&path, depth) {
+                    Ok(level) => self.stack.push(level),
+                    Err(e) => self.deferred_error = Some(e),
+                }
+            }
+
+            let relative = self.relative(&path);
+            let included = self.options.include.is_empty() || self.options.include.iter().any(|glob| glob.matches(relative));
+            if included {
+                return Some(Ok(WalkEntry { path, depth, file_type }));
+            }
+            if let Some(error) = self.deferred_error.take() {
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+enum WalkState {
+    Idle(Box<Walker>),
+    Walking(IoFuture<WalkBatch>),
+    Finished,
+}
+
+///a recursive walk through a directory, from [`walk_dir`]. It's configured by the builder methods, which have to be
+///called before it's first polled.
+#[must_use = "streams do nothing unless polled"]
+pub struct WalkDir {
+    state: WalkState,
+    batch: VecDeque<Result<WalkEntry, WalkError>>,
+}
+
+///walks everything below `root` depth first, with each directory's entry coming before its contents. `root`
+///itself isn't included.
+pub fn walk_dir (root: impl AsRef<Path>) -> WalkDir {
+    WalkDir {
+        state: WalkState::Idle(Box::new(Walker {
+            root: root.as_ref().to_path_buf(),
+            options: WalkOptions::default(),
+            started: false,
+            stack: vec![],
+            deferred_error: None,
+        })),
+        batch: VecDeque::new(),
+    }
+}
+
+impl WalkDir {
+    fn options (&mut self) -> &mut WalkOptions {
+        match &mut self.state {
+            WalkState::Idle(walker) if !walker.started => &mut walker.options,
+            _ => panic!("tried to configure a walk_dir which has already started"),
+        }
+    }
+
+    ///doesn't go more than `depth` levels below the root - a depth of 1 only gives the root's own entries
+    pub fn max_depth (mut self, depth: usize) -> Self {
+        self.options().max_depth = Some(depth);
+        self
+    }
+
+    ///treats symlinks as whatever they point to, following them into directories. Off by default.
+    pub fn follow_links (mut self, follow: bool) -> Self {
+        self.options().follow_links = follow;
+        self
+    }
+
+    ///only gives entries matching one of the include patterns, although directories which don't match are still
+    ///walked through. See [`Glob`] for the syntax.
+    pub fn include (mut self, pattern: &str) -> Self {
+        self.options().include.push(Glob::new(pattern));
+        self
+    }
+
+    ///skips entries matching any exclude pattern, along with everything inside them
+    pub fn exclude (mut self, pattern: &str) -> Self {
+        self.options().exclude.push(Glob::new(pattern));
+        self
+    }
+
+    ///gives each directory's entries sorted by file name, rather than in whatever order the OS has them
+    pub fn sorted (mut self, sorted: bool) -> Self {
+        self.options().sorted = sorted;
+        self
+    }
+}
+
+impl Stream for WalkDir {
+    type Item = Result<WalkEntry, WalkError>;
+
+    fn poll_next (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(entry) = self.batch.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+
+            match std::mem::replace(&mut self.state, WalkState::Finished) {
+                WalkState::Finished => return Poll::Ready(None),
+                WalkState::Idle(walker) => {
+                    self.state = WalkState::Walking(IoFuture::new(WalkBatch { walker, max_entries: BATCH_SIZE }));
+                }
+                WalkState::Walking(mut walking) => match Pin::new(&mut walking).poll(cx) {
+                    Poll::Pending => {
+                        self.state = WalkState::Walking(walking);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((walker, batch)) => {
+                        if batch.len() == BATCH_SIZE {
+                            self.state = WalkState::Idle(walker);
+                        }
+                        self.batch.extend(batch);
+                    }
+                },
+            }
+        }
+    }
+}
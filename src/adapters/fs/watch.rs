@@ -0,0 +1,254 @@
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CString, OsStr};
+use std::io::ErrorKind;
+use std::future::Future;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use crate::adapters::io_worker::requests::{AddWatches, StartWatch};
+use crate::adapters::reactor::{self, Interest};
+use crate::adapters::IoFuture;
+use crate::stream::Stream;
+use crate::debug;
+
+const WATCH_MASK: u32 = libc::IN_CREATE | libc::IN_MODIFY | libc::IN_DELETE | libc::IN_DELETE_SELF
+    | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+///big enough for plenty of events, and always at least one with the longest possible name
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WatchEvent {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    ///both ends of the rename were inside what's being watched - otherwise it shows up as a create or a remove
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+}
+
+///the watch descriptors of an inotify instance, and the paths they're for
+pub type Watches = HashMap<i32, PathBuf>;
+
+pub fn add_watch (inotify: &OwnedFd, path: &Path, watches: &mut Watches) -> Result<(), std::io::Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, e))?;
+    //SAFETY: just a syscall, with a nul terminated path which outlives it
+    let wd = unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), c_path.as_ptr(), WATCH_MASK) };
+    if wd == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    watches.insert(wd, path.to_path_buf());
+    Ok(())
+}
+
+///watches `path`, and every directory below it
+pub fn add_watches_recursively (inotify: &OwnedFd, path: &Path, watches: &mut Watches) -> Result<(), std::io::Error> {
+    add_watch(inotify, path, watches)?;
+
+    for entry in std::fs::read_dir(path)? {
+        let Ok(entry) = entry else { continue };
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            //a directory we can't watch shouldn't stop us watching the rest
+            if let Err(e) = add_watches_recursively(inotify, &entry.path(), watches) {
+                debug!("fs", "unable to watch {}: {e}", entry.path().display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_events (inotify: &OwnedFd, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+    //SAFETY: the kernel writes at most `buffer.len()` bytes into it
+    let n = unsafe { libc::read(inotify.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+    usize::try_from(n).map_err(|_| std::io::Error::last_os_error())
+}
+
+///changes under a path, from [`watch`]. It ends once nothing is being watched any more, eg. once the path itself is
+///removed.
+#[must_use = "streams do nothing unless polled"]
+pub struct Watcher {
+    inotify: Arc<OwnedFd>,
+    watches: Watches,
+    recursive: bool,
+    buffer: Box<[u8]>,
+    events: VecDeque<Result<WatchEvent, std::io::Error>>,
+    ///new directories found by the last read, which need watching before reading any more - otherwise we couldn't
+    ///tell where their events came from
+    new_dirs: Vec<PathBuf>,
+    adding: Option<IoFuture<AddWatches>>,
+}
+
+///watches `path` for changes. If `recursive`, that includes everything below it, including directories created
+///later.
+pub async fn watch (path: impl AsRef<Path>, recursive: bool) -> Result<Watcher, std::io::Error> {
+    let (inotify, watches) = IoFuture::new(StartWatch {
+        path: path.as_ref().to_path_buf(),
+        recursive,
+    }).await?;
+
+    Ok(Watcher {
+        inotify: Arc::new(inotify),
+        watches,
+        recursive,
+        buffer: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+        events: VecDeque::new(),
+        new_dirs: vec![],
+        adding: None,
+    })
+}
+
+impl Watcher {
+    ///stops watching a directory which has been moved out of what we're watching, along with everything below it
+    fn moved_out (&mut self, dir: &Path) {
+        for (&wd, path) in &self.watches {
+            if path.starts_with(dir) {
+                //SAFETY: just a syscall. The watch's `IN_IGNORED` removes it from `watches` later.
+                unsafe { libc::inotify_rm_watch(self.inotify.as_raw_fd(), wd) };
+            }
+        }
+    }
+
+    ///keeps the paths of watches below a directory which has been renamed up to date
+    fn moved_within (&mut self, from: &Path, to: &Path) {
+        for path in self.watches.values_mut() {
+            if let Ok(rest) = path.strip_prefix(from) {
+                *path = to.join(rest);
+            }
+        }
+    }
+
+    ///turns the raw events from one read into [`WatchEvent`]s
+    fn parse (&mut self, n: usize) {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+        //a move's `IN_MOVED_FROM` and `IN_MOVED_TO` share a cookie, and come next to each other
+        let mut moved_from: Option<(u32, PathBuf, bool)> = None;
+        let mut offset = 0;
+
+        while offset + HEADER <= n {
+            //SAFETY: the kernel only gives us whole events, and we've checked there's a header's worth left
+            let event: libc::inotify_event = unsafe { std::ptr::read_unaligned(self.buffer[offset..].as_ptr().cast()) };
+            let name_start = offset + HEADER;
+            offset = name_start + event.len as usize;
+
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                self.events.push_back(Err(std::io::Error::other("inotify queue overflowed, so some events were lost")));
+                continue;
+            }
+            if event.mask & libc::IN_IGNORED != 0 {
+                self.watches.remove(&event.wd);
+                continue;
+            }
+            let Some(dir) = self.watches.get(&event.wd) else {
+                continue;
+            };
+
+            //the name is padded out with nuls
+            let name = &self.buffer[name_start..offset];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            let path = if name.is_empty() { dir.clone() } else { dir.join(OsStr::from_bytes(name)) };
+
+            if let Some((_, from, was_dir)) = moved_from.take_if(|(cookie, ..)| *cookie != event.cookie || event.mask & libc::IN_MOVED_TO == 0) {
+                //moved out of anywhere we're watching
+                if was_dir {
+                    self.moved_out(&from);
+                }
+                self.events.push_back(Ok(WatchEvent::Removed(from)));
+            }
+
+            let is_dir = event.mask & libc::IN_ISDIR != 0;
+            let event = match event.mask {
+                mask if mask & libc::IN_CREATE != 0 => WatchEvent::Created(path.clone()),
+                mask if mask & libc::IN_MODIFY != 0 => WatchEvent::Modified(path.clone()),
+                mask if mask & libc::IN_DELETE != 0 => WatchEvent::Removed(path.clone()),
+                //a watched directory's parent reports it being removed too, so this is only news for the root
+                mask if mask & libc::IN_DELETE_SELF != 0 => {
+                    if path.parent().is_some_and(|parent| self.watches.values().any(|watched| watched == parent)) {
+                        continue;
+                    }
+                    WatchEvent::Removed(path.clone())
+                }
+                mask if mask & libc::IN_MOVED_FROM != 0 => {
+                    moved_from = Some((event.cookie, path, is_dir));
+                    continue;
+                }
+                mask if mask & libc::IN_MOVED_TO != 0 => match moved_from.take() {
+                    Some((_, from, _)) => {
+                        if is_dir {
+                            self.moved_within(&from, &path);
+                        }
+                        WatchEvent::Renamed { from, to: path.clone() }
+                    }
+                    None => WatchEvent::Created(path.clone()),
+                },
+                _ => continue,
+            };
+
+            //new directories need watching too, including anything already put inside them. Ones renamed within
+            //what we're watching already are.
+            if self.recursive && is_dir && matches!(event, WatchEvent::Created(_)) {
+                self.new_dirs.push(path);
+            }
+            self.events.push_back(Ok(event));
+        }
+
+        if let Some((_, from, was_dir)) = moved_from {
+            if was_dir {
+                self.moved_out(&from);
+            }
+            self.events.push_back(Ok(WatchEvent::Removed(from)));
+        }
+    }
+}
+
+impl Stream for Watcher {
+    type Item = Result<WatchEvent, std::io::Error>;
+
+    fn poll_next (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if this.adding.is_none() && !this.new_dirs.is_empty() {
+                this.adding = Some(IoFuture::new(AddWatches {
+                    inotify: this.inotify.clone(),
+                    dirs: std::mem::take(&mut this.new_dirs),
+                }));
+            }
+            //hold the events back until then, so that once a new directory shows up, changes inside it do too
+            if let Some(adding) = &mut this.adding {
+                let Poll::Ready(watches) = Pin::new(adding).poll(cx) else {
+                    return Poll::Pending;
+                };
+                this.adding = None;
+                this.watches.extend(watches);
+            }
+
+            if let Some(event) = this.events.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if this.watches.is_empty() {
+                return Poll::Ready(None);
+            }
+
+            match read_events(&this.inotify, &mut this.buffer) {
+                Ok(n) => this.parse(n),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    reactor::wake_when_ready(&*this.inotify, Interest::Readable, cx)?;
+                    return Poll::Pending;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        reactor::deregister(&*self.inotify);
+    }
+}
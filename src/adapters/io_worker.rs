@@ -1,211 +1,366 @@
 use std::collections::HashMap;
-use std::fs::File as StdFile;
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::sync::{Arc, LazyLock, Mutex};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::fmt::{Display, Formatter};
+use std::fs::{File as StdFile, ReadDir};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
 use std::task::Waker;
+use std::time::Instant;
 use crate::id::{Id, IdGenerator};
+use crate::metrics::{IoMetrics, IoSnapshot};
+use crate::{debug, trace};
 
-pub struct IoReq {
-    waker: Waker,
-    options: IoReqOptions
-}
-
-pub enum IoReqOptions {
-    OpenFile(PathBuf),
-    CreateFile(PathBuf),
-    ReadFile(Id, usize),
-    FullyReadFile(Id),
-    WriteFile(Id, Vec<u8>),
-    FileMetadata(Id),
-    FileToStd(Id),
-    SetFileLen(Id, u64),
-    ChangeDir(PathBuf, DirChange),
-    Copy(PathBuf, PathBuf),
-    RemoveFile(PathBuf),
-    Rename(PathBuf, PathBuf),
-}
-
-pub enum DirChange {
-    CreateDir,
-    CreateDirAll,
-    RemoveDir,
-    RemoveDirAll,
-}
-
-pub enum IoOutcome {
-    FileOpenedOrCreated(Result<Id, std::io::Error>),
-    FileBytesRead(Result<Vec<u8>, std::io::Error>),
-    FileBytesWritten(Result<usize, std::io::Error>),
-    FileMetadata(Result<std::fs::Metadata, std::io::Error>),
-    FileToStd(StdFile),
-    FileLenSet(Result<(), std::io::Error>),
-    DirChanged(Result<(), std::io::Error>),
-    Copied(Result<u64, std::io::Error>),
-    Removed(Result<(), std::io::Error>),
-    Renamed(Result<(), std::io::Error>),
+pub mod requests;
+
+static IO_METRICS: IoMetrics = IoMetrics::new();
+
+pub fn io_metrics() -> IoSnapshot {
+    IO_METRICS.snapshot()
+}
+
+///the error given when a request refers to a file or directory listing the io thread doesn't know about - eg. one
+///which has been closed or turned into a [`std::fs::File`]
+#[derive(Debug, Copy, Clone)]
+pub struct BadHandle(pub Id);
+
+impl Display for BadHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no open file with id {}", self.0)
+    }
+}
+
+impl std::error::Error for BadHandle {}
+
+impl BadHandle {
+    pub fn is_bad_handle (error: &std::io::Error) -> bool {
+        matches!(error.get_ref(), Some(inner) if inner.is::<Self>())
+    }
+}
+
+impl From<BadHandle> for std::io::Error {
+    fn from(value: BadHandle) -> Self {
+        Self::new(std::io::ErrorKind::InvalidInput, value)
+    }
+}
+
+///something the io thread can do, which always produces exactly one `Output`
+pub trait IoRequest: Send + 'static {
+    type Output: Send + 'static;
+
+    ///the file this request operates on, if any - all requests for one file go to the same worker so they stay in order
+    fn file (&self) -> Option<Id> {
+        None
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output;
+
+    ///starts the request - by default it's run to completion straight away on the worker, but a request can instead
+    ///hand `completion` off to finish elsewhere
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) where Self: Sized {
+        let output = self.run(worker);
+        completion.complete(output);
+    }
+}
+
+///hands the output of a request back to whoever sent it, wherever the request ends up finishing
+pub struct Completion<T> {
+    ///`None` if nobody is going to wait on the outcome
+    slot: Option<Arc<ResultSlot<T>>>,
+    sent_at: Instant,
+}
+
+impl<T> Completion<T> {
+    pub fn complete (self, output: T) {
+        //if nobody wants it, it's dropped either way
+        let _ = self.complete_if_wanted(output);
+    }
+
+    ///hands `output` over, unless nobody is waiting on it any more - eg. its future was dropped - in which case it's
+    ///given back. Checking and handing over happen together, so the future can't be dropped in between.
+    pub fn complete_if_wanted (self, output: T) -> Result<(), T> {
+        trace!("io", "finished request after {:?}", self.sent_at.elapsed());
+        IO_METRICS.latency.record(self.sent_at.elapsed());
+        IO_METRICS.completed.fetch_add(1, Ordering::Relaxed);
+
+        //detached requests have nobody waiting on the outcome
+        match self.slot {
+            Some(slot) => slot.fill(output),
+            None => Err(output),
+        }
+    }
+}
+
+struct SlotState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    ///whether whoever was waiting on it has given up
+    abandoned: bool,
+}
+
+///where a worker leaves the output of a request for the future waiting on it
+pub struct ResultSlot<T> {
+    state: Mutex<SlotState<T>>,
+}
+
+impl<T> ResultSlot<T> {
+    pub const fn new (waker: Waker) -> Self {
+        Self {
+            state: Mutex::new(SlotState {
+                value: None,
+                waker: Some(waker),
+                abandoned: false,
+            }),
+        }
+    }
+
+    fn fill (&self, value: T) -> Result<(), T> {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            if state.abandoned {
+                return Err(value);
+            }
+            state.value = Some(value);
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    ///takes the output if it's there, otherwise makes sure `waker` gets woken once it is
+    pub fn take_or_register (&self, waker: &Waker) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        let value = state.value.take();
+        if value.is_none() && !state.waker.as_ref().is_some_and(|old| old.will_wake(waker)) {
+            state.waker = Some(waker.clone());
+        }
+        value
+    }
+
+    ///says nobody is going to take the output - anything already there is dropped
+    pub fn abandon (&self) {
+        let mut state = self.state.lock().unwrap();
+        state.abandoned = true;
+        state.value = None;
+        state.waker = None;
+    }
+}
+
+///a request and where to put its output, with the types erased so every kind of request can go down the same channel
+trait Job: Send {
+    fn file (&self) -> Option<Id>;
+    fn start (self: Box<Self>, worker: &mut Worker);
+}
+
+struct TypedJob<R: IoRequest> {
+    request: R,
+    completion: Completion<R::Output>,
+}
+
+impl<R: IoRequest> Job for TypedJob<R> {
+    fn file (&self) -> Option<Id> {
+        self.request.file()
+    }
+
+    fn start (self: Box<Self>, worker: &mut Worker) {
+        self.request.start(worker, self.completion);
+    }
 }
 
 pub struct IoThread {
-    requests_tx: Sender<(Id, IoReq)>,
-    #[allow(clippy::type_complexity)]
-    results: Arc<Mutex<(Receiver<(Id, IoOutcome)>, HashMap<Id, IoOutcome>)>>,
-    task_id_generator: Arc<Mutex<IdGenerator>>,
+    workers: Vec<Sender<Box<dyn Job>>>,
+    next_worker: AtomicUsize,
+}
+
+const DEFAULT_WORKERS: usize = 4;
+static WORKER_COUNT: OnceLock<usize> = OnceLock::new();
+
+///sets how many workers the io thread pool uses. This has to happen before any io is done, and returns
+///`false` if the pool size has already been decided.
+pub fn set_io_workers (n_workers: usize) -> bool {
+    WORKER_COUNT.set(n_workers.max(1)).is_ok()
+}
+
+///the open files and directory listings, shared between every io worker
+#[derive(Default)]
+pub struct SharedFiles {
+    files: Mutex<HashMap<Id, Arc<Mutex<StdFile>>>>,
+    dirs: Mutex<HashMap<Id, Arc<Mutex<ReadDir>>>>,
+    file_id_generator: Mutex<IdGenerator>,
+}
+
+impl SharedFiles {
+    pub fn insert (&self, file: StdFile) -> Id {
+        let id = self.file_id_generator.lock().unwrap().next();
+        self.files.lock().unwrap().insert(id, Arc::new(Mutex::new(file)));
+        id
+    }
+}
+
+pub struct Worker {
+    shared: Arc<SharedFiles>,
+    read_buffer: Vec<u8>,
+}
+
+impl Worker {
+    pub fn insert_file (&self, file: StdFile) -> Id {
+        self.shared.insert(file)
+    }
+
+    pub fn shared_files (&self) -> Arc<SharedFiles> {
+        self.shared.clone()
+    }
+
+    pub fn file (&self, id: Id) -> Result<Arc<Mutex<StdFile>>, BadHandle> {
+        self.shared.files.lock().unwrap().get(&id).cloned().ok_or(BadHandle(id))
+    }
+
+    pub fn with_file<T> (
+        &self,
+        id: Id,
+        f: impl FnOnce(&mut StdFile) -> Result<T, std::io::Error>,
+    ) -> Result<T, std::io::Error> {
+        let file = self.file(id)?;
+        let mut file = file.lock().unwrap();
+        f(&mut file)
+    }
+
+    pub fn remove_file (&self, id: Id) -> Result<StdFile, BadHandle> {
+        let file = self.shared.files.lock().unwrap().remove(&id).ok_or(BadHandle(id))?;
+        //every request for this file goes through the same worker, so nobody else can be holding it
+        Ok(Arc::try_unwrap(file)
+            .unwrap_or_else(|_| unreachable!("file {id} was in use by another worker"))
+            .into_inner()
+            .unwrap())
+    }
+
+    pub fn insert_dir (&self, dir: ReadDir) -> Id {
+        let id = self.shared.file_id_generator.lock().unwrap().next();
+        self.shared.dirs.lock().unwrap().insert(id, Arc::new(Mutex::new(dir)));
+        id
+    }
+
+    pub fn with_dir<T> (&self, id: Id, f: impl FnOnce(&mut ReadDir) -> T) -> Result<T, BadHandle> {
+        let dir = self.shared.dirs.lock().unwrap().get(&id).cloned().ok_or(BadHandle(id))?;
+        let mut dir = dir.lock().unwrap();
+        Ok(f(&mut dir))
+    }
+
+    pub fn remove_dir (&self, id: Id) -> Result<(), BadHandle> {
+        self.shared.dirs.lock().unwrap().remove(&id).map(drop).ok_or(BadHandle(id))
+    }
+
+    ///a scratch buffer of `len` bytes, reused between requests
+    pub fn read_buffer (&mut self, len: usize) -> &mut [u8] {
+        if self.read_buffer.len() < len {
+            self.read_buffer.resize(len, 0);
+        }
+        &mut self.read_buffer[0..len]
+    }
 }
 
 impl IoThread {
-    #[allow(clippy::too_many_lines)]
     pub fn get() -> &'static Self {
         static INSTANCE: LazyLock<IoThread> = LazyLock::new(|| {
-            let (requests_tx, requests_rx) = channel::<(Id, IoReq)>();
-            let (results_tx, results_rx) = channel::<(Id, IoOutcome)>();
-
-            std::thread::Builder::new()
-                .name("fs_io_thread".into())
-                .spawn(move || {
-                    let mut file_id_generator = IdGenerator::default();
-                    let mut files = HashMap::new();
-                    let mut read_buffer = vec![0_u8; 128];
-                    
-                    for (request_id, request) in requests_rx {
-                        match request.options {
-                            IoReqOptions::OpenFile(path) => {
-                                let result = StdFile::open(path).map(|stdfile| {
-                                    let id = file_id_generator.next();
-                                    files.insert(id, stdfile);
-                                    id
-                                });
-
-                                let _ = results_tx.send((request_id, IoOutcome::FileOpenedOrCreated(result)));
-                                request.waker.wake();
-                            },
-                            IoReqOptions::CreateFile(path) => {
-                                let result = StdFile::create(path).map(|stdfile| {
-                                    let id = file_id_generator.next();
-                                    files.insert(id, stdfile);
-                                    id
-                                });
-
-                                let _ = results_tx.send((request_id, IoOutcome::FileOpenedOrCreated(result)));
-                                request.waker.wake();
-                            }
-                            IoReqOptions::ReadFile(file, max_bytes) => {
-                                if let Some(file) = files.get_mut(&file) {
-                                    if read_buffer.len() < max_bytes {
-                                        read_buffer.resize(max_bytes, 0);
-                                    }
-                                    
-                                    let result = file.read(&mut read_buffer[0..max_bytes]).map(|n| {
-                                        read_buffer[0..n].to_vec()
-                                    });
-
-                                    let _ = results_tx.send((request_id, IoOutcome::FileBytesRead(result)));
-                                    request.waker.wake();
-                                }
-                            }
-                            IoReqOptions::FullyReadFile(file) => {
-                                if let Some(file) = files.get_mut(&file) {
-                                    let mut stack_read_buffer = [0; 1024];
-                                    
-                                    let mut contents = vec![];
-                                    let error = loop {
-                                        match file.read(&mut stack_read_buffer) {
-                                            Ok(0) => break None,
-                                            Ok(n) => contents.extend_from_slice(&read_buffer[..n]),
-                                            Err(e) => break Some(e),
-                                        }
-                                    };
-                                    
-                                    let result = error
-                                        .map_or(
-                                            Ok(contents),
-                                            Err
-                                        );
-                                    
-                                    let _ = results_tx.send((request_id, IoOutcome::FileBytesRead(result)));
-                                    request.waker.wake();
-                                }
-                            }
-                            IoReqOptions::WriteFile(file, buffer) => {
-                                if let Some(file) = files.get_mut(&file) {
-                                    let result = file.write(&buffer);
-                                    let _ = results_tx.send((request_id, IoOutcome::FileBytesWritten(result)));
-                                    request.waker.wake();
-                                }
-                            }
-                            IoReqOptions::FileMetadata(file) => {
-                                if let Some(file) = files.get_mut(&file) {
-                                    let result = file.metadata();
-                                    let _ = results_tx.send((request_id, IoOutcome::FileMetadata(result)));
-                                    request.waker.wake();
-                                }
-                            }
-                            IoReqOptions::FileToStd(file) => {
-                                if let Some(file) = files.remove(&file) {
-                                    let _ = results_tx.send((request_id, IoOutcome::FileToStd(file)));
-                                    request.waker.wake();
-                                }
-                            }
-                            IoReqOptions::SetFileLen(file, size) => {
-                                if let Some(file) = files.get_mut(&file) {
-                                    let res = file.set_len(size);
-                                    let _ = results_tx.send((request_id, IoOutcome::FileLenSet(res)));
-                                    request.waker.wake();
-                                }
-                            }
-                            IoReqOptions::ChangeDir(path, change) => {
-                                let res = match change {
-                                    DirChange::CreateDir => std::fs::create_dir(path),
-                                    DirChange::CreateDirAll => std::fs::create_dir_all(path),
-                                    DirChange::RemoveDir => std::fs::remove_dir(path),
-                                    DirChange::RemoveDirAll => std::fs::remove_dir_all(path),
-                                };
-
-                                let _ = results_tx.send((request_id, IoOutcome::DirChanged(res)));
-                                request.waker.wake();
-                            }
-                            IoReqOptions::Copy(from, to) => {
-                                let res = std::fs::copy(from, to);
-                                let _ = results_tx.send((request_id, IoOutcome::Copied(res)));
-                                request.waker.wake();
-                            }
-                            IoReqOptions::RemoveFile(path) => {
-                                let res = std::fs::remove_file(path);
-                                let _ = results_tx.send((request_id, IoOutcome::Removed(res)));
-                                request.waker.wake();
-                            }
-                            IoReqOptions::Rename(from, to) => {
-                                let res = std::fs::rename(from, to);
-                                let _ = results_tx.send((request_id, IoOutcome::Renamed(res)));
-                                request.waker.wake();
+            let n_workers = *WORKER_COUNT.get_or_init(|| DEFAULT_WORKERS);
+            let shared = Arc::new(SharedFiles::default());
+
+            let workers = (0..n_workers).map(|i| {
+                let (requests_tx, requests_rx) = channel::<Box<dyn Job>>();
+                let mut worker = Worker {
+                    shared: shared.clone(),
+                    read_buffer: vec![0_u8; 128],
+                };
+
+                std::thread::Builder::new()
+                    .name(format!("fs_io_thread_{i}"))
+                    .spawn(move || {
+                        for job in requests_rx {
+                            IO_METRICS.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                            debug!("io", "worker {i} handling request");
+
+                            //anything still in flight on io_uring for this file has to finish first, to keep them in order
+                            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+                            if let (Some(file), Some(uring)) = (job.file(), crate::adapters::uring::Uring::get()) {
+                                uring.wait_for_file(file);
                             }
+
+                            job.start(&mut worker);
                         }
-                    }
-                })
-                .expect("unable to spawn fs_io_thread");
+                    })
+                    .expect("unable to spawn fs_io_thread");
+
+                requests_tx
+            }).collect();
 
             IoThread {
-                requests_tx,
-                results: Arc::new(Mutex::new((results_rx, HashMap::new()))),
-                task_id_generator: Arc::new(Mutex::new(IdGenerator::default())),
+                workers,
+                next_worker: AtomicUsize::new(0),
             }
         });
 
         &INSTANCE
     }
-    
-    pub fn send_request (&self, options: IoReqOptions, waker: Waker) -> Id {
-        let id = self.task_id_generator.lock().unwrap().next();
-        let _ = self.requests_tx.send((id, IoReq {
-            waker, options
-        }));
-        id
+
+    ///sends a request, with its output going into `slot`
+    pub fn send_request<R: IoRequest> (&self, request: R, slot: Arc<ResultSlot<R::Output>>) {
+        self.send(TypedJob {
+            request,
+            completion: Completion {
+                slot: Some(slot),
+                sent_at: Instant::now(),
+            },
+        });
+    }
+
+    ///sends a request whose outcome nobody cares about - eg. closing a file on drop
+    pub fn send_detached_request<R: IoRequest> (&self, request: R) {
+        self.send(TypedJob {
+            request,
+            completion: Completion {
+                slot: None,
+                sent_at: Instant::now(),
+            },
+        });
+    }
+
+    fn send<R: IoRequest> (&self, job: TypedJob<R>) {
+        let worker = job.file().map_or_else(
+            || self.next_worker.fetch_add(1, Ordering::Relaxed),
+            |file| {
+                let mut hasher = DefaultHasher::new();
+                file.hash(&mut hasher);
+                #[allow(clippy::cast_possible_truncation)]
+                let hash = hasher.finish() as usize;
+                hash
+            },
+        ) % self.workers.len();
+
+        IO_METRICS.queue_depth.fetch_add(1, Ordering::Relaxed);
+        let _ = self.workers[worker].send(Box::new(job));
     }
-    
-    pub fn try_recv_result (&self, id: Id) -> Option<IoOutcome> {
-        let (rx, map) = &mut *self.results.lock().unwrap();
-        map.extend(rx.try_iter());
-        map.remove(&id)
+}
+
+///flushes and closes the file, reporting any errors which dropping it would swallow
+fn close_file (mut file: StdFile) -> Result<(), std::io::Error> {
+    file.flush()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::IntoRawFd;
+
+        let fd = file.into_raw_fd();
+        //SAFETY: we own the descriptor, and nobody else can use it after `into_raw_fd`
+        if unsafe { libc::close(fd) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
     }
-}
\ No newline at end of file
+    #[cfg(not(unix))]
+    drop(file);
+
+    Ok(())
+}
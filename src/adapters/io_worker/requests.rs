@@ -0,0 +1,840 @@
+use std::fs::{DirEntry, File as StdFile, FileType, OpenOptions, Permissions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(target_os = "linux")]
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::adapters::file::Metadata;
+#[cfg(unix)]
+use crate::adapters::file::Mmap;
+use crate::adapters::fs::FileTimes;
+use crate::adapters::fs::walk_dir::{WalkEntry, WalkError, Walker};
+#[cfg(target_os = "linux")]
+use crate::adapters::fs::watch::{add_watch, add_watches_recursively, Watches};
+use crate::adapters::io_worker::{close_file, Completion, IoRequest, Worker};
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+use crate::adapters::uring::Uring;
+use crate::id::Id;
+use crate::debug;
+
+pub struct OpenFile(pub PathBuf);
+
+impl IoRequest for OpenFile {
+    type Output = Result<Id, std::io::Error>;
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        StdFile::open(self.0).map(|stdfile| worker.insert_file(stdfile))
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        match Uring::get() {
+            Some(uring) => uring.open(&self.0, libc::O_RDONLY, worker.shared_files(), completion),
+            None => completion.complete(self.run(worker)),
+        }
+    }
+}
+
+pub struct CreateFile(pub PathBuf);
+
+impl IoRequest for CreateFile {
+    type Output = Result<Id, std::io::Error>;
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        StdFile::create(self.0).map(|stdfile| worker.insert_file(stdfile))
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        match Uring::get() {
+            Some(uring) => uring.open(&self.0, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, worker.shared_files(), completion),
+            None => completion.complete(self.run(worker)),
+        }
+    }
+}
+
+pub struct OpenWithOptions(pub PathBuf, pub OpenOptions);
+
+impl IoRequest for OpenWithOptions {
+    type Output = Result<Id, std::io::Error>;
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        self.1.open(self.0).map(|stdfile| worker.insert_file(stdfile))
+    }
+}
+
+pub struct ReadFile {
+    pub file: Id,
+    pub max_bytes: usize,
+}
+
+impl IoRequest for ReadFile {
+    type Output = Result<Vec<u8>, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.file)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        let file = worker.file(self.file)?;
+        let read_buffer = worker.read_buffer(self.max_bytes);
+        let n = file.lock().unwrap().read(read_buffer)?;
+        Ok(read_buffer[0..n].to_vec())
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        match (Uring::get(), worker.file(self.file)) {
+            (Some(uring), Ok(file)) => uring.read(self.file, file, self.max_bytes, completion),
+            (_, Err(e)) => completion.complete(Err(e.into())),
+            (None, Ok(_)) => completion.complete(self.run(worker)),
+        }
+    }
+}
+
+pub struct FullyReadFile(pub Id);
+
+impl IoRequest for FullyReadFile {
+    type Output = Result<Vec<u8>, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| {
+            let mut contents = vec![];
+            file.read_to_end(&mut contents)?;
+            Ok(contents)
+        })
+    }
+}
+
+pub struct WriteFile(pub Id, pub Vec<u8>);
+
+impl IoRequest for WriteFile {
+    type Output = Result<usize, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.write(&self.1))
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        match (Uring::get(), worker.file(self.0)) {
+            (Some(uring), Ok(file)) => uring.write(self.0, file, self.1, completion),
+            (_, Err(e)) => completion.complete(Err(e.into())),
+            (None, Ok(_)) => completion.complete(self.run(worker)),
+        }
+    }
+}
+
+///writes all of the bytes, rather than however many one write manages
+pub struct WriteAllFile(pub Id, pub Vec<u8>);
+
+impl IoRequest for WriteAllFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.write_all(&self.1))
+    }
+}
+
+pub struct SeekFile(pub Id, pub SeekFrom);
+
+impl IoRequest for SeekFile {
+    type Output = Result<u64, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.seek(self.1))
+    }
+}
+
+///reads from `offset` without moving the file position
+pub struct ReadFileAt {
+    pub file: Id,
+    pub offset: u64,
+    pub max_bytes: usize,
+}
+
+impl IoRequest for ReadFileAt {
+    type Output = Result<Vec<u8>, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.file)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        let file = worker.file(self.file)?;
+        let read_buffer = worker.read_buffer(self.max_bytes);
+        let n = read_at(&file.lock().unwrap(), read_buffer, self.offset)?;
+        Ok(read_buffer[0..n].to_vec())
+    }
+}
+
+///writes at `offset` without moving the file position
+pub struct WriteFileAt(pub Id, pub u64, pub Vec<u8>);
+
+impl IoRequest for WriteFileAt {
+    type Output = Result<usize, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| write_at(file, &self.2, self.1))
+    }
+}
+
+#[cfg(unix)]
+fn read_at (file: &StdFile, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn write_at (file: &StdFile, buf: &[u8], offset: u64) -> Result<usize, std::io::Error> {
+    std::os::unix::fs::FileExt::write_at(file, buf, offset)
+}
+
+//windows only has versions which move the file position, so put it back afterwards
+#[cfg(windows)]
+fn read_at (mut file: &StdFile, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+    let position = file.stream_position()?;
+    let res = std::os::windows::fs::FileExt::seek_read(file, buf, offset);
+    file.seek(SeekFrom::Start(position))?;
+    res
+}
+
+#[cfg(windows)]
+fn write_at (mut file: &StdFile, buf: &[u8], offset: u64) -> Result<usize, std::io::Error> {
+    let position = file.stream_position()?;
+    let res = std::os::windows::fs::FileExt::seek_write(file, buf, offset);
+    file.seek(SeekFrom::Start(position))?;
+    res
+}
+
+pub struct FileMetadata(pub Id);
+
+impl IoRequest for FileMetadata {
+    type Output = Result<Metadata, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.metadata().map(Into::into))
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        match (Uring::get(), worker.file(self.0)) {
+            (Some(uring), Ok(file)) => uring.statx(self.0, file, completion),
+            (_, Err(e)) => completion.complete(Err(e.into())),
+            (None, Ok(_)) => completion.complete(self.run(worker)),
+        }
+    }
+}
+
+pub struct FileToStd(pub Id);
+
+impl IoRequest for FileToStd {
+    type Output = Result<StdFile, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        Ok(worker.remove_file(self.0)?)
+    }
+}
+
+pub struct SetFileLen(pub Id, pub u64);
+
+impl IoRequest for SetFileLen {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.set_len(self.1))
+    }
+}
+
+pub struct SyncAll(pub Id);
+
+impl IoRequest for SyncAll {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.sync_all())
+    }
+}
+
+pub struct SyncData(pub Id);
+
+impl IoRequest for SyncData {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.sync_data())
+    }
+}
+
+pub struct FlushFile(pub Id);
+
+impl IoRequest for FlushFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, Write::flush)
+    }
+}
+
+///duplicates the file's handle under a new id - both share the same file position
+pub struct CloneFile(pub Id);
+
+impl IoRequest for CloneFile {
+    type Output = Result<Id, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        let clone = worker.with_file(self.0, |file| file.try_clone())?;
+        Ok(worker.insert_file(clone))
+    }
+}
+
+///waits for an advisory lock on the file. If it's free, it's just taken on the worker, otherwise the wait happens
+///on its own thread through a duplicate of the handle, so it doesn't hold up everything else on the worker - the
+///lock belongs to the open file rather than the handle, so it's still held once the duplicate is closed.
+///
+///If whoever asked has stopped waiting by the time the lock is taken, it's released again straight away.
+pub struct LockFile {
+    pub file: Id,
+    pub shared: bool,
+}
+
+impl LockFile {
+    fn lock (&self, file: &StdFile) -> Result<(), std::io::Error> {
+        if self.shared { file.lock_shared() } else { file.lock() }
+    }
+
+    fn try_lock (&self, file: &StdFile) -> Result<(), TryLockError> {
+        if self.shared { file.try_lock_shared() } else { file.try_lock() }
+    }
+
+    ///hands over a lock which has been taken - or releases it, if whoever asked has stopped waiting, as nobody would
+    ///know to otherwise
+    fn hand_over (&self, file: &StdFile, completion: Completion<Result<(), std::io::Error>>) {
+        if completion.complete_if_wanted(Ok(())).is_err() {
+            debug!("fs", "releasing lock on {} which nobody is waiting for any more", self.file);
+            let _ = file.unlock();
+        }
+    }
+}
+
+impl IoRequest for LockFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.file)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.file, |file| self.lock(file))
+    }
+
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        let file = match worker.file(self.file) {
+            Ok(file) => file,
+            Err(e) => return completion.complete(Err(e.into())),
+        };
+        let file = file.lock().unwrap();
+
+        //no need for a thread if nobody else has it
+        let duplicate = match self.try_lock(&file) {
+            Ok(()) => return self.hand_over(&file, completion),
+            Err(TryLockError::WouldBlock) => file.try_clone(),
+            Err(TryLockError::Error(e)) => Err(e),
+        };
+        drop(file);
+        let duplicate = match duplicate {
+            Ok(duplicate) => duplicate,
+            Err(e) => return completion.complete(Err(e)),
+        };
+
+        std::thread::Builder::new()
+            .name(format!("fs_lock_{}", self.file))
+            .spawn(move || match self.lock(&duplicate) {
+                Ok(()) => self.hand_over(&duplicate, completion),
+                Err(e) => completion.complete(Err(e)),
+            })
+            .expect("unable to spawn fs_lock thread");
+    }
+}
+
+///takes an exclusive advisory lock if nobody else has any lock on the file, giving whether it did
+pub struct TryLockFile(pub Id);
+
+impl IoRequest for TryLockFile {
+    type Output = Result<bool, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| match file.try_lock() {
+            Ok(()) => Ok(true),
+            Err(TryLockError::WouldBlock) => Ok(false),
+            Err(TryLockError::Error(e)) => Err(e),
+        })
+    }
+}
+
+pub struct UnlockFile(pub Id);
+
+impl IoRequest for UnlockFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        worker.with_file(self.0, |file| file.unlock())
+    }
+}
+
+///maps the whole file into memory - see [`Mmap`] for why that needs care. Only [`File::map_readonly`]'s callers,
+///who've promised to take that care, send it.
+///
+///[`File::map_readonly`]: crate::adapters::file::File::map_readonly
+#[cfg(unix)]
+pub struct MapFile(pub Id);
+
+#[cfg(unix)]
+impl IoRequest for MapFile {
+    type Output = Result<Mmap, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        //SAFETY: up to whoever asked for it, as above
+        worker.with_file(self.0, |file| unsafe { Mmap::map(file) })
+    }
+}
+
+pub struct CloseFile(pub Id);
+
+impl IoRequest for CloseFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        close_file(worker.remove_file(self.0)?)
+    }
+}
+
+pub enum DirChange {
+    CreateDir,
+    CreateDirAll,
+    RemoveDir,
+    RemoveDirAll,
+}
+
+pub struct ChangeDir(pub PathBuf, pub DirChange);
+
+impl IoRequest for ChangeDir {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        let Self(path, change) = self;
+        match change {
+            DirChange::CreateDir => std::fs::create_dir(path),
+            DirChange::CreateDirAll => std::fs::create_dir_all(path),
+            DirChange::RemoveDir => std::fs::remove_dir(path),
+            DirChange::RemoveDirAll => std::fs::remove_dir_all(path),
+        }
+    }
+}
+
+pub struct CopyFile(pub PathBuf, pub PathBuf);
+
+impl IoRequest for CopyFile {
+    type Output = Result<u64, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::copy(self.0, self.1)
+    }
+}
+
+pub struct RemoveFile(pub PathBuf);
+
+impl IoRequest for RemoveFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::remove_file(self.0)
+    }
+}
+
+pub struct Rename(pub PathBuf, pub PathBuf);
+
+impl IoRequest for Rename {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::rename(self.0, self.1)
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn start (self, worker: &mut Worker, completion: Completion<Self::Output>) {
+        match Uring::get() {
+            Some(uring) => uring.rename(&self.0, &self.1, completion),
+            None => completion.complete(self.run(worker)),
+        }
+    }
+}
+
+pub struct ReadWholeFile(pub PathBuf);
+
+impl IoRequest for ReadWholeFile {
+    type Output = Result<Vec<u8>, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::read(self.0)
+    }
+}
+
+pub struct WriteWholeFile(pub PathBuf, pub Vec<u8>);
+
+impl IoRequest for WriteWholeFile {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::write(self.0, self.1)
+    }
+}
+
+///stats a path without opening it, so it works for directories, sockets and files we can't read
+pub struct PathMetadata {
+    pub path: PathBuf,
+    ///whether to give the metadata of what a symlink points to, rather than the link itself
+    pub follow_symlinks: bool,
+}
+
+impl IoRequest for PathMetadata {
+    type Output = Result<Metadata, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        if self.follow_symlinks {
+            std::fs::metadata(self.path).map(Into::into)
+        } else {
+            std::fs::symlink_metadata(self.path).map(Into::into)
+        }
+    }
+}
+
+pub struct Canonicalize(pub PathBuf);
+
+impl IoRequest for Canonicalize {
+    type Output = Result<PathBuf, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::canonicalize(self.0)
+    }
+}
+
+pub struct HardLink(pub PathBuf, pub PathBuf);
+
+impl IoRequest for HardLink {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::hard_link(self.0, self.1)
+    }
+}
+
+///makes a symlink at `.1` pointing to `.0`
+pub struct Symlink(pub PathBuf, pub PathBuf);
+
+impl IoRequest for Symlink {
+    type Output = Result<(), std::io::Error>;
+
+    #[cfg(unix)]
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::os::unix::fs::symlink(self.0, self.1)
+    }
+
+    //windows needs to know up front whether the link is to a directory
+    #[cfg(windows)]
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        if std::fs::metadata(&self.0).is_ok_and(|metadata| metadata.is_dir()) {
+            std::os::windows::fs::symlink_dir(self.0, self.1)
+        } else {
+            std::os::windows::fs::symlink_file(self.0, self.1)
+        }
+    }
+}
+
+pub struct ReadLink(pub PathBuf);
+
+impl IoRequest for ReadLink {
+    type Output = Result<PathBuf, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::read_link(self.0)
+    }
+}
+
+pub struct SetPermissions(pub PathBuf, pub Permissions);
+
+impl IoRequest for SetPermissions {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        std::fs::set_permissions(self.0, self.1)
+    }
+}
+
+///gives `to` the permissions of `from`, and its owner where we're allowed to change that. If `from` doesn't exist,
+///`to` is left alone.
+pub struct CopyPermissions {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl IoRequest for CopyPermissions {
+    type Output = Result<(), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        let existing = match std::fs::metadata(&self.from) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        //before the permissions, as changing the owner can clear setuid and setgid bits
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            //only root can give files away, so that's fine to miss
+            match std::os::unix::fs::chown(&self.to, Some(existing.uid()), Some(existing.gid())) {
+                Err(e) if e.kind() != std::io::ErrorKind::PermissionDenied => return Err(e),
+                _ => {}
+            }
+        }
+
+        std::fs::set_permissions(&self.to, existing.permissions())
+    }
+}
+
+pub struct TryExists(pub PathBuf);
+
+impl IoRequest for TryExists {
+    type Output = Result<bool, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        self.0.try_exists()
+    }
+}
+
+pub struct SetTimes(pub PathBuf, pub FileTimes);
+
+impl IoRequest for SetTimes {
+    type Output = Result<(), std::io::Error>;
+
+    #[cfg(unix)]
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        use std::os::unix::ffi::OsStrExt;
+
+        //set by path, as opening it could need permissions we don't have or block forever on a fifo
+        let path = std::ffi::CString::new(self.0.as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let times = self.1.to_timespecs()?;
+        //SAFETY: just a syscall, with a nul terminated path and two timespecs which outlive it
+        if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        //there's no way to set them by path elsewhere, so it has to be opened for writing
+        let file = OpenOptions::new().write(true).open(self.0)?;
+        file.set_times(self.1.to_std())
+    }
+}
+
+pub struct OpenDir(pub PathBuf);
+
+impl IoRequest for OpenDir {
+    type Output = Result<Id, std::io::Error>;
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        std::fs::read_dir(self.0).map(|dir| worker.insert_dir(dir))
+    }
+}
+
+///the next `max_entries` entries from an [`OpenDir`], along with their file types. An empty batch means the listing
+///is finished.
+pub struct ReadDirBatch {
+    pub dir: Id,
+    pub max_entries: usize,
+}
+
+impl IoRequest for ReadDirBatch {
+    type Output = Result<Vec<Result<(DirEntry, FileType), std::io::Error>>, std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.dir)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        Ok(worker.with_dir(self.dir, |dir| {
+            dir.by_ref()
+                .take(self.max_entries)
+                .map(|entry| {
+                    let entry = entry?;
+                    //usually this comes along with the entry, but on some filesystems it needs a `stat`
+                    let file_type = entry.file_type()?;
+                    Ok((entry, file_type))
+                })
+                .collect()
+        })?)
+    }
+}
+
+///carries a walk on for up to `max_entries` more entries, handing the walker back afterwards. A smaller batch means
+///the walk is finished.
+pub struct WalkBatch {
+    pub walker: Box<Walker>,
+    pub max_entries: usize,
+}
+
+impl IoRequest for WalkBatch {
+    type Output = (Box<Walker>, Vec<Result<WalkEntry, WalkError>>);
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        let mut walker = self.walker;
+        let batch = std::iter::from_fn(|| walker.next_entry()).take(self.max_entries).collect();
+        (walker, batch)
+    }
+}
+
+///sets up the inotify instance on the io thread, as adding the watches can mean walking a whole tree
+#[cfg(target_os = "linux")]
+pub struct StartWatch {
+    pub path: PathBuf,
+    pub recursive: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl IoRequest for StartWatch {
+    type Output = Result<(OwnedFd, Watches), std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        //SAFETY: just a syscall, and we check the result before using it
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        //SAFETY: we just made it, so we own it
+        let inotify = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut watches = Watches::new();
+        if self.recursive && std::fs::metadata(&self.path)?.is_dir() {
+            add_watches_recursively(&inotify, &self.path, &mut watches)?;
+        } else {
+            add_watch(&inotify, &self.path, &mut watches)?;
+        }
+
+        Ok((inotify, watches))
+    }
+}
+
+///watches directories which turned up after the watch started, and everything already inside them
+#[cfg(target_os = "linux")]
+pub struct AddWatches {
+    pub inotify: Arc<OwnedFd>,
+    pub dirs: Vec<PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl IoRequest for AddWatches {
+    type Output = Watches;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        let mut watches = Watches::new();
+        for dir in self.dirs {
+            //it may well have gone again already
+            if let Err(e) = add_watches_recursively(&self.inotify, &dir, &mut watches) {
+                debug!("fs", "unable to watch {}: {e}", dir.display());
+            }
+        }
+        watches
+    }
+}
+
+pub struct CloseDir(pub Id);
+
+impl IoRequest for CloseDir {
+    type Output = Result<(), std::io::Error>;
+
+    fn file (&self) -> Option<Id> {
+        Some(self.0)
+    }
+
+    fn run (self, worker: &mut Worker) -> Self::Output {
+        Ok(worker.remove_dir(self.0)?)
+    }
+}
+
+pub struct DirEntryMetadata(pub Arc<DirEntry>);
+
+impl IoRequest for DirEntryMetadata {
+    type Output = Result<Metadata, std::io::Error>;
+
+    fn run (self, _worker: &mut Worker) -> Self::Output {
+        self.0.metadata().map(Into::into)
+    }
+}
@@ -1,8 +1,13 @@
-use std::net::{TcpListener as StdTcpListener, TcpStream as StdTcpStream, ToSocketAddrs};
+use std::net::{SocketAddr, TcpListener as StdTcpListener, TcpStream as StdTcpStream, ToSocketAddrs};
 use std::future::Future;
-use std::io::{ErrorKind, Read};
+use std::io::{ErrorKind, Read, Write};
+use std::ops::Range;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use crate::adapters::file::File;
+use crate::adapters::reactor::{self, Interest};
+use crate::debug;
+use crate::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 pub struct TcpStream {
     stdstream: StdTcpStream,
@@ -17,39 +22,49 @@ impl TcpStream {
     }
 
     pub async fn read (&mut self, output: &mut [u8]) -> Result<usize, std::io::Error> {
-        struct AsyncRead<'a> {
-            listener: &'a mut StdTcpStream,
-            output: &'a mut [u8],
-        }
+        AsyncReadExt::read(self, output).await
+    }
 
-        impl Future for AsyncRead<'_> {
-            type Output = Result<usize, std::io::Error>;
+    pub async fn write (&mut self, input: &[u8]) -> Result<usize, std::io::Error> {
+        AsyncWriteExt::write(self, input).await
+    }
 
-            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                let mut internal_output = self.output.to_vec(); //TODO: don't allocate a new buffer on every read?
-                //could use a [0; self.output.len()], but that could stack overflow i think
-                let res = self.listener.read(&mut internal_output);
-                match res {
-                    Ok(n) => {
-                        Poll::Ready(Ok(n))
-                    },
-                    Err(e) => {
-                        if e.kind() == ErrorKind::WouldBlock {
-                            cx.waker().wake_by_ref(); //spurious wake? what's that 😇😇😇
-                            Poll::Pending
-                        } else {
-                            Poll::Ready(Err(e))
-                        }
-                    },
+    pub async fn write_all (&mut self, input: &[u8]) -> Result<(), std::io::Error> {
+        AsyncWriteExt::write_all(self, input).await
+    }
+}
 
-                }
-            }
+impl AsyncRead for TcpStream {
+    fn poll_read (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, std::io::Error>> {
+        match self.stdstream.read(buf) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                reactor::wake_when_ready(&self.stdstream, Interest::Readable, cx)?;
+                Poll::Pending
+            },
+            res => Poll::Ready(res),
         }
-        
-        AsyncRead {
-            listener: &mut self.stdstream,
-            output,
-        }.await
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        match self.stdstream.write(buf) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                reactor::wake_when_ready(&self.stdstream, Interest::Writable, cx)?;
+                Poll::Pending
+            },
+            res => Poll::Ready(res),
+        }
+    }
+
+    fn poll_flush (mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(self.stdstream.flush())
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        reactor::deregister(&self.stdstream);
     }
 }
 
@@ -67,6 +82,10 @@ impl TcpListener {
             stdlistener
         })
     }
+
+    pub fn local_addr (&self) -> Result<SocketAddr, std::io::Error> {
+        self.stdlistener.local_addr()
+    }
     
     pub async fn accept (&self) -> Result<TcpStream, std::io::Error> {
         struct TcpListenerAccept<'listener> {
@@ -79,14 +98,11 @@ impl TcpListener {
             fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
                 match self.stdlistener.accept() {
                     Ok((s, _)) => Poll::Ready(TcpStream::from_std(s)),
-                    Err(e) => {
-                        if e.kind() == ErrorKind::WouldBlock {
-                            cx.waker().wake_by_ref();
-                            Poll::Pending
-                        } else {
-                            Poll::Ready(Err(e))
-                        }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        reactor::wake_when_ready(self.stdlistener, Interest::Readable, cx)?;
+                        Poll::Pending
                     },
+                    Err(e) => Poll::Ready(Err(e)),
                 }
             }
         }
@@ -97,6 +113,115 @@ impl TcpListener {
     }
 }
 
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        reactor::deregister(&self.stdlistener);
+    }
+}
+
+///how much [`copy_file_to_socket`] reads at once when it can't use `sendfile`
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+///sends `range` of `file` down `stream`, giving how many bytes were sent - fewer than the range if the file ends
+///first. On Linux this uses `sendfile`, so the data never comes through us, and otherwise it's read through the io
+///thread a chunk at a time. Either way the file's position isn't used or moved.
+pub async fn copy_file_to_socket (file: &File, stream: &mut TcpStream, range: Range<u64>) -> Result<u64, std::io::Error> {
+    let mut offset = range.start;
+
+    #[cfg(target_os = "linux")]
+    {
+        //a handle of our own, so the file doesn't have to be borrowed from the io thread for the whole copy
+        let duplicate = file.try_clone().await?.into_std().await?;
+        let send_file = SendFile {
+            file: &duplicate,
+            stream,
+            offset: &mut offset,
+            end: range.end,
+        };
+
+        match send_file.await {
+            Ok(()) => return Ok(offset - range.start),
+            //not every kind of file can be sent, but those can still be copied
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) => {
+                debug!("net", "unable to use sendfile, so copying instead: {e}");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    copy_in_chunks(file, stream, &mut offset, range.end).await?;
+    Ok(offset - range.start)
+}
+
+///sends from `offset` to `end` of the file with `sendfile`, moving `offset` along as it goes
+#[cfg(target_os = "linux")]
+struct SendFile<'a> {
+    file: &'a std::fs::File,
+    stream: &'a TcpStream,
+    offset: &'a mut u64,
+    end: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl Future for SendFile<'_> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        use std::os::fd::AsRawFd;
+
+        //linux won't send more than this in one go anyway
+        const MAX_SEND: u64 = 0x7fff_f000;
+
+        loop {
+            let remaining = self.end.saturating_sub(*self.offset).min(MAX_SEND);
+            if remaining == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut offset = libc::off_t::try_from(*self.offset)
+                .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "offset is too big for sendfile"))?;
+            //SAFETY: just a syscall on two descriptors we're keeping open, and an offset which outlives it
+            #[allow(clippy::cast_possible_truncation)]
+            let n = unsafe {
+                libc::sendfile(self.stream.stdstream.as_raw_fd(), self.file.as_raw_fd(), &raw mut offset, remaining as usize)
+            };
+
+            match n {
+                //the file's ended
+                0 => return Poll::Ready(Ok(())),
+                n if n > 0 => *self.offset += n.unsigned_abs() as u64,
+                _ => {
+                    let e = std::io::Error::last_os_error();
+                    match e.kind() {
+                        ErrorKind::WouldBlock => {
+                            reactor::wake_when_ready(&self.stream.stdstream, Interest::Writable, cx)?;
+                            return Poll::Pending;
+                        }
+                        ErrorKind::Interrupted => {}
+                        _ => return Poll::Ready(Err(e)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+///the portable way of doing [`copy_file_to_socket`], moving `offset` along as it goes
+async fn copy_in_chunks (file: &File, stream: &mut TcpStream, offset: &mut u64, end: u64) -> Result<(), std::io::Error> {
+    while *offset < end {
+        let len = usize::try_from(end - *offset).unwrap_or(usize::MAX).min(COPY_CHUNK_SIZE);
+        let chunk = file.read_at(*offset, len).await?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        stream.write_all(&chunk).await?;
+        *offset += chunk.len() as u64;
+    }
+
+    Ok(())
+}
+
 pub async fn fully_read_from_socket (addr: impl ToSocketAddrs + Send) -> Result<Vec<u8>, std::io::Error> {
     let listener = TcpListener::bind(addr)?;
     let mut stream = listener.accept().await?;
@@ -110,7 +235,83 @@ pub async fn fully_read_from_socket (addr: impl ToSocketAddrs + Send) -> Result<
             Err(e) => return Err(e),
         }
     }
-    println!("[tcp] done");
+    debug!("net", "finished reading from socket");
     
     Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+    use crate::adapters::file::File;
+    use crate::adapters::net::{copy_file_to_socket, copy_in_chunks, TcpListener};
+    use crate::executor::block_on;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn echo_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            //give the server time to block on the read
+            std::thread::sleep(Duration::from_millis(50));
+            stream.write_all(b"ping").unwrap();
+            let mut reply = [0_u8; 4];
+            stream.read_exact(&mut reply).unwrap();
+            reply
+        });
+
+        let received = block_on(async move {
+            let mut stream = listener.accept().await.unwrap();
+            let mut received = [0_u8; 4];
+            let n = stream.read(&mut received).await.unwrap();
+            stream.write_all(&received[..n]).await.unwrap();
+            received
+        });
+
+        assert_eq!(&received, b"ping");
+        assert_eq!(&client.join().unwrap(), b"ping");
+    }
+
+    #[test]
+    fn file_to_socket() {
+        let path = TempPath::new("file_to_socket");
+        let contents: Vec<u8> = (0..300_000_u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            //let the socket's buffer fill up, so the sender has to wait on it
+            std::thread::sleep(Duration::from_millis(50));
+            let mut received = vec![];
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let sent = block_on({
+            let path = path.to_path_buf();
+            async move {
+                let file = File::open(&path).await.unwrap();
+                let mut stream = listener.accept().await.unwrap();
+
+                let mut sent = vec![copy_file_to_socket(&file, &mut stream, 10..250_000).await.unwrap()];
+                //running off the end of the file just stops early
+                sent.push(copy_file_to_socket(&file, &mut stream, 299_990..400_000).await.unwrap());
+
+                let mut offset = 1000;
+                copy_in_chunks(&file, &mut stream, &mut offset, 200_000).await.unwrap();
+                sent.push(offset - 1000);
+                sent
+            }
+        });
+
+        assert_eq!(sent, [249_990, 10, 199_000]);
+        let expected = [&contents[10..250_000], &contents[299_990..], &contents[1000..200_000]].concat();
+        assert!(client.join().unwrap() == expected);
+    }
+}
@@ -0,0 +1,193 @@
+use std::task::Context;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Interest {
+    Readable,
+    Writable,
+}
+
+///makes sure the task gets woken once `source` is ready for `interest`. Call this after an operation gives
+///`WouldBlock`, then return `Poll::Pending`.
+#[cfg(target_os = "linux")]
+pub fn wake_when_ready (source: &impl std::os::fd::AsRawFd, interest: Interest, cx: &Context<'_>) -> Result<(), std::io::Error> {
+    epoll::Reactor::get().register(source.as_raw_fd(), interest, cx.waker())
+}
+
+///without a reactor, all we can do is ask to be polled again straight away
+#[cfg(not(target_os = "linux"))]
+pub fn wake_when_ready<S> (_source: &S, _interest: Interest, cx: &Context<'_>) -> Result<(), std::io::Error> {
+    cx.waker().wake_by_ref();
+    Ok(())
+}
+
+///stops watching `source` - this has to happen before it gets closed, as the descriptor could be reused
+#[cfg(target_os = "linux")]
+pub fn deregister (source: &impl std::os::fd::AsRawFd) {
+    epoll::Reactor::get().deregister(source.as_raw_fd());
+}
+
+#[cfg(not(target_os = "linux"))]
+pub const fn deregister<S> (_source: &S) {}
+
+#[cfg(target_os = "linux")]
+mod epoll {
+    use std::collections::HashMap;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::sync::{LazyLock, Mutex};
+    use std::task::Waker;
+    use crate::adapters::reactor::Interest;
+    use crate::{error, trace};
+
+    const MAX_EVENTS: usize = 64;
+
+    ///the tasks waiting on one descriptor
+    #[derive(Default)]
+    struct Registration {
+        reader: Option<Waker>,
+        writer: Option<Waker>,
+    }
+
+    impl Registration {
+        ///the epoll events we still need to hear about
+        #[allow(clippy::cast_sign_loss)]
+        const fn events (&self) -> u32 {
+            let mut events = libc::EPOLLONESHOT as u32;
+            if self.reader.is_some() {
+                events |= libc::EPOLLIN as u32;
+            }
+            if self.writer.is_some() {
+                events |= libc::EPOLLOUT as u32;
+            }
+            events
+        }
+    }
+
+    pub struct Reactor {
+        epoll: OwnedFd,
+        ///everything which has been added to `epoll`
+        registrations: Mutex<HashMap<RawFd, Registration>>,
+    }
+
+    impl Reactor {
+        pub fn get () -> &'static Self {
+            static INSTANCE: LazyLock<Reactor> = LazyLock::new(|| {
+                //SAFETY: just a syscall, and we check the result before using it
+                let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+                assert_ne!(epoll, -1, "unable to create epoll instance: {}", std::io::Error::last_os_error());
+
+                std::thread::Builder::new()
+                    .name("net_reactor".into())
+                    .spawn(|| Reactor::get().run())
+                    .expect("unable to spawn net_reactor");
+
+                Reactor {
+                    //SAFETY: we just made it, so we own it
+                    epoll: unsafe { OwnedFd::from_raw_fd(epoll) },
+                    registrations: Mutex::new(HashMap::new()),
+                }
+            });
+
+            &INSTANCE
+        }
+
+        fn ctl (&self, op: i32, fd: RawFd, events: u32) -> Result<(), std::io::Error> {
+            #[allow(clippy::cast_sign_loss)]
+            let mut event = libc::epoll_event {
+                events,
+                u64: fd as u64,
+            };
+            //SAFETY: `event` outlives the call, and the kernel copies it
+            if unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &raw mut event) } == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        pub fn register (&self, fd: RawFd, interest: Interest, waker: &Waker) -> Result<(), std::io::Error> {
+            trace!("net", "waiting for fd {fd} to be {interest:?}");
+
+            let mut registrations = self.registrations.lock().unwrap();
+            let op = if registrations.contains_key(&fd) { libc::EPOLL_CTL_MOD } else { libc::EPOLL_CTL_ADD };
+            let registration = registrations.entry(fd).or_default();
+
+            let slot = match interest {
+                Interest::Readable => &mut registration.reader,
+                Interest::Writable => &mut registration.writer,
+            };
+            *slot = Some(waker.clone());
+
+            //level triggered, so if it's already ready by now we hear about it straight away
+            let events = registration.events();
+            let res = self.ctl(op, fd, events);
+            if res.is_err() && op == libc::EPOLL_CTL_ADD {
+                registrations.remove(&fd);
+            }
+            res
+        }
+
+        pub fn deregister (&self, fd: RawFd) {
+            if self.registrations.lock().unwrap().remove(&fd).is_some() {
+                let _ = self.ctl(libc::EPOLL_CTL_DEL, fd, 0);
+            }
+        }
+
+        ///takes the wakers for whatever `events` says is ready, re-arming the descriptor for anyone left waiting
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        fn ready (&self, fd: RawFd, events: u32) -> Vec<Waker> {
+            let hangup = (libc::EPOLLERR | libc::EPOLLHUP) as u32;
+            let mut wakers = vec![];
+
+            let mut registrations = self.registrations.lock().unwrap();
+            let Some(registration) = registrations.get_mut(&fd) else {
+                return wakers;
+            };
+
+            if events & (libc::EPOLLIN as u32 | hangup) != 0 {
+                wakers.extend(registration.reader.take());
+            }
+            if events & (libc::EPOLLOUT as u32 | hangup) != 0 {
+                wakers.extend(registration.writer.take());
+            }
+
+            if registration.reader.is_some() || registration.writer.is_some() {
+                let events = registration.events();
+                if let Err(e) = self.ctl(libc::EPOLL_CTL_MOD, fd, events) {
+                    error!("net", "unable to re-arm fd {fd}: {e}");
+                    wakers.extend(registration.reader.take());
+                    wakers.extend(registration.writer.take());
+                }
+            }
+            drop(registrations);
+
+            wakers
+        }
+
+        fn run (&self) {
+            let mut events = [libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+
+            loop {
+                //SAFETY: the kernel writes at most `MAX_EVENTS` events into `events`
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                let n = unsafe { libc::epoll_wait(self.epoll.as_raw_fd(), events.as_mut_ptr(), MAX_EVENTS as i32, -1) };
+                if n == -1 {
+                    let e = std::io::Error::last_os_error();
+                    if e.kind() == std::io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    error!("net", "reactor stopped: {e}");
+                    return;
+                }
+
+                #[allow(clippy::cast_sign_loss)]
+                for event in &events[0..n as usize] {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let fd = event.u64 as RawFd;
+                    //wake outside the lock, in case the waker wants to register again
+                    for waker in self.ready(fd, event.events) {
+                        waker.wake();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString};
+use std::fs::File as StdFile;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, LazyLock, Mutex};
+use io_uring::{opcode, squeue, types, IoUring, Probe};
+use crate::adapters::file::Metadata;
+use crate::adapters::io_worker::{Completion, SharedFiles};
+use crate::id::Id;
+use crate::{error, info};
+
+const RING_ENTRIES: u32 = 256;
+///the `user_data` for reads of the eventfd which tells the ring thread there's more to submit
+const WAKE_KEY: u64 = u64::MAX;
+
+///files with an operation in flight on the ring - anything else touching them has to wait
+#[derive(Default)]
+struct BusyFiles {
+    files: Mutex<HashSet<Id>>,
+    now_idle: Condvar,
+}
+
+impl BusyFiles {
+    fn mark_busy (&self, id: Id) {
+        let files = self.files.lock().unwrap();
+        self.now_idle.wait_while(files, |files| files.contains(&id)).unwrap().insert(id);
+    }
+
+    fn mark_idle (&self, id: Id) {
+        self.files.lock().unwrap().remove(&id);
+        self.now_idle.notify_all();
+    }
+
+    fn wait_for (&self, id: Id) {
+        let files = self.files.lock().unwrap();
+        drop(self.now_idle.wait_while(files, |files| files.contains(&id)).unwrap());
+    }
+}
+
+enum Op {
+    Read {
+        file_id: Id,
+        //kept around so the descriptor stays open until the kernel is done with it
+        file: Arc<Mutex<StdFile>>,
+        buf: Vec<u8>,
+        completion: Completion<Result<Vec<u8>, std::io::Error>>,
+    },
+    Write {
+        file_id: Id,
+        file: Arc<Mutex<StdFile>>,
+        buf: Vec<u8>,
+        completion: Completion<Result<usize, std::io::Error>>,
+    },
+    Open {
+        path: CString,
+        flags: i32,
+        files: Arc<SharedFiles>,
+        completion: Completion<Result<Id, std::io::Error>>,
+    },
+    Rename {
+        from: CString,
+        to: CString,
+        completion: Completion<Result<(), std::io::Error>>,
+    },
+    Statx {
+        file_id: Id,
+        file: Arc<Mutex<StdFile>>,
+        //boxed so it stays put while the kernel fills it in
+        buf: Box<libc::statx>,
+        completion: Completion<Result<Metadata, std::io::Error>>,
+    },
+}
+
+///`statx` on a descriptor rather than a path wants an empty path alongside `AT_EMPTY_PATH`
+const EMPTY_PATH: &CStr = c"";
+
+///turns a completion result into the number it returned, or the error it reported
+#[allow(clippy::cast_sign_loss)]
+fn cqe_result (result: i32) -> Result<usize, std::io::Error> {
+    if result < 0 {
+        Err(std::io::Error::from_raw_os_error(-result))
+    } else {
+        Ok(result as usize)
+    }
+}
+
+impl Op {
+    ///the submission for this op - it points into buffers owned by the op, so the op has to outlive it
+    #[allow(clippy::cast_possible_truncation)]
+    fn entry (&mut self) -> squeue::Entry {
+        match self {
+            Self::Read { file, buf, .. } => {
+                let fd = file.lock().unwrap().as_raw_fd();
+                //an offset of -1 means use (and move) the file position, like `read(2)`
+                opcode::Read::new(types::Fd(fd), buf.as_mut_ptr(), buf.len() as u32)
+                    .offset(u64::MAX)
+                    .build()
+            }
+            Self::Write { file, buf, .. } => {
+                let fd = file.lock().unwrap().as_raw_fd();
+                opcode::Write::new(types::Fd(fd), buf.as_ptr(), buf.len() as u32)
+                    .offset(u64::MAX)
+                    .build()
+            }
+            Self::Open { path, flags, .. } => {
+                opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), path.as_ptr())
+                    .flags(*flags | libc::O_CLOEXEC)
+                    .mode(0o666)
+                    .build()
+            }
+            Self::Rename { from, to, .. } => {
+                opcode::RenameAt::new(types::Fd(libc::AT_FDCWD), from.as_ptr(), types::Fd(libc::AT_FDCWD), to.as_ptr())
+                    .build()
+            }
+            Self::Statx { file, buf, .. } => {
+                let fd = file.lock().unwrap().as_raw_fd();
+                opcode::Statx::new(types::Fd(fd), EMPTY_PATH.as_ptr(), (&raw mut **buf).cast())
+                    .flags(libc::AT_EMPTY_PATH)
+                    .mask(libc::STATX_BASIC_STATS | libc::STATX_BTIME)
+                    .build()
+            }
+        }
+    }
+
+    fn finish (self, result: i32, busy: &BusyFiles) {
+        let result = cqe_result(result);
+
+        match self {
+            Self::Read { file_id, file, mut buf, completion } => {
+                drop(file);
+                completion.complete(result.map(|n| {
+                    buf.truncate(n);
+                    buf
+                }));
+                busy.mark_idle(file_id);
+            }
+            Self::Write { file_id, file, completion, .. } => {
+                drop(file);
+                completion.complete(result);
+                busy.mark_idle(file_id);
+            }
+            Self::Open { files, completion, .. } => {
+                completion.complete(result.map(|fd| {
+                    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                    //SAFETY: the kernel just gave us this descriptor, so nobody else owns it
+                    let file = unsafe { StdFile::from_raw_fd(fd as RawFd) };
+                    files.insert(file)
+                }));
+            }
+            Self::Rename { completion, .. } => {
+                completion.complete(result.map(|_| ()));
+            }
+            Self::Statx { file_id, file, buf, completion } => {
+                drop(file);
+                completion.complete(result.map(|_| Metadata::from_statx(&buf)));
+                busy.mark_idle(file_id);
+            }
+        }
+    }
+
+    ///completes the op with an error saying the ring stopped because of `error`. If it was submitted the kernel may
+    ///still be using its buffers, so they're leaked rather than freed.
+    fn fail (self, error: &std::io::Error, busy: &BusyFiles, submitted: bool) {
+        let stopped = || std::io::Error::new(error.kind(), format!("io_uring stopped: {error}"));
+
+        match self {
+            Self::Read { file_id, buf, completion, .. } => {
+                if submitted { std::mem::forget(buf) }
+                completion.complete(Err(stopped()));
+                busy.mark_idle(file_id);
+            }
+            Self::Write { file_id, buf, completion, .. } => {
+                if submitted { std::mem::forget(buf) }
+                completion.complete(Err(stopped()));
+                busy.mark_idle(file_id);
+            }
+            Self::Open { path, completion, .. } => {
+                if submitted { std::mem::forget(path) }
+                completion.complete(Err(stopped()));
+            }
+            Self::Rename { from, to, completion } => {
+                if submitted { std::mem::forget((from, to)) }
+                completion.complete(Err(stopped()));
+            }
+            Self::Statx { file_id, buf, completion, .. } => {
+                if submitted { std::mem::forget(buf) }
+                completion.complete(Err(stopped()));
+                busy.mark_idle(file_id);
+            }
+        }
+    }
+}
+
+pub struct Uring {
+    ops_tx: Sender<Op>,
+    eventfd: OwnedFd,
+    busy: Arc<BusyFiles>,
+    ///cleared if the ring thread hits an error, after which everything goes through the io threads instead
+    alive: Arc<AtomicBool>,
+}
+
+fn path_to_cstring (path: &Path) -> Result<CString, std::io::Error> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+fn push (ring: &mut IoUring, entry: &squeue::Entry) -> Result<(), std::io::Error> {
+    //SAFETY: every entry points into an op which we keep in `pending` until its completion comes back
+    while unsafe { ring.submission().push(entry) }.is_err() {
+        ring.submit()?;
+    }
+    Ok(())
+}
+
+///submits ops from `ops_rx` and finishes them as they complete, until something goes wrong - which leaves anything
+///still in flight in `pending`
+fn run_ring (
+    mut ring: IoUring,
+    ops_rx: &Receiver<Op>,
+    eventfd: RawFd,
+    busy: &BusyFiles,
+    pending: &mut HashMap<u64, Op>,
+) -> Result<(), std::io::Error> {
+    let mut next_key = 0_u64;
+    //leaked, as the kernel could still write to it after we give up on the ring
+    let wake_buf: &'static mut [u8; 8] = Box::leak(Box::new([0_u8; 8]));
+
+    let wake_entry = |wake_buf: &mut [u8; 8]| {
+        opcode::Read::new(types::Fd(eventfd), wake_buf.as_mut_ptr(), 8)
+            .build()
+            .user_data(WAKE_KEY)
+    };
+    push(&mut ring, &wake_entry(wake_buf))?;
+
+    loop {
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+
+        let done: Vec<(u64, i32)> = ring.completion().map(|cqe| (cqe.user_data(), cqe.result())).collect();
+        for (key, result) in done {
+            if key == WAKE_KEY {
+                for mut op in ops_rx.try_iter() {
+                    let entry = op.entry().user_data(next_key);
+                    pending.insert(next_key, op);
+                    next_key = next_key.wrapping_add(1) % WAKE_KEY;
+                    push(&mut ring, &entry)?;
+                }
+                push(&mut ring, &wake_entry(wake_buf))?;
+            } else if let Some(op) = pending.remove(&key) {
+                op.finish(result, busy);
+            }
+        }
+    }
+}
+
+impl Uring {
+    ///the ring, or `None` if this kernel can't do everything we need from it
+    pub fn get () -> Option<&'static Self> {
+        static INSTANCE: LazyLock<Option<Uring>> = LazyLock::new(|| match Uring::start() {
+            Ok(uring) => Some(uring),
+            Err(e) => {
+                info!("io", "io_uring unavailable, falling back to io threads: {e}");
+                None
+            }
+        });
+
+        INSTANCE.as_ref().filter(|uring| uring.alive.load(Ordering::Acquire))
+    }
+
+    fn start () -> Result<Self, std::io::Error> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        if !ring.params().is_feature_rw_cur_pos() {
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "kernel can't read from the file position"));
+        }
+
+        let mut probe = Probe::new();
+        ring.submitter().register_probe(&mut probe)?;
+        for code in [opcode::Read::CODE, opcode::Write::CODE, opcode::OpenAt::CODE, opcode::RenameAt::CODE, opcode::Statx::CODE] {
+            if !probe.is_supported(code) {
+                return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, format!("kernel doesn't support opcode {code}")));
+            }
+        }
+
+        //SAFETY: just a syscall, and we check the result before using it
+        let eventfd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if eventfd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        //SAFETY: we just made it, so we own it
+        let eventfd = unsafe { OwnedFd::from_raw_fd(eventfd) };
+
+        let (ops_tx, ops_rx) = channel();
+        let busy = Arc::new(BusyFiles::default());
+
+        let alive = Arc::new(AtomicBool::new(true));
+
+        let thread_eventfd = eventfd.as_raw_fd();
+        let (thread_busy, thread_alive) = (busy.clone(), alive.clone());
+        std::thread::Builder::new()
+            .name("io_uring_thread".into())
+            .spawn(move || {
+                let mut pending = HashMap::new();
+                if let Err(e) = run_ring(ring, &ops_rx, thread_eventfd, &thread_busy, &mut pending) {
+                    error!("io", "io_uring thread stopped, falling back to io threads: {e}");
+                    thread_alive.store(false, Ordering::Release);
+
+                    //nothing's going to complete these now, so they'd hang otherwise
+                    for (_, op) in pending.drain() {
+                        op.fail(&e, &thread_busy, true);
+                    }
+                    //as would anything sent by workers which got hold of the ring before it stopped
+                    for op in &ops_rx {
+                        op.fail(&e, &thread_busy, false);
+                    }
+                }
+            })?;
+
+        Ok(Self { ops_tx, eventfd, busy, alive })
+    }
+
+    fn submit (&self, op: Op) {
+        let _ = self.ops_tx.send(op);
+
+        let one = 1_u64;
+        //SAFETY: writing 8 bytes from a u64 which outlives the call
+        unsafe {
+            libc::write(self.eventfd.as_raw_fd(), (&raw const one).cast(), 8);
+        }
+    }
+
+    ///blocks until nothing is in flight for this file
+    pub fn wait_for_file (&self, file: Id) {
+        self.busy.wait_for(file);
+    }
+
+    pub fn read (&self, file_id: Id, file: Arc<Mutex<StdFile>>, max_bytes: usize, completion: Completion<Result<Vec<u8>, std::io::Error>>) {
+        self.busy.mark_busy(file_id);
+        self.submit(Op::Read {
+            file_id,
+            file,
+            buf: vec![0; max_bytes],
+            completion,
+        });
+    }
+
+    pub fn write (&self, file_id: Id, file: Arc<Mutex<StdFile>>, buf: Vec<u8>, completion: Completion<Result<usize, std::io::Error>>) {
+        self.busy.mark_busy(file_id);
+        self.submit(Op::Write {
+            file_id,
+            file,
+            buf,
+            completion,
+        });
+    }
+
+    pub fn statx (&self, file_id: Id, file: Arc<Mutex<StdFile>>, completion: Completion<Result<Metadata, std::io::Error>>) {
+        self.busy.mark_busy(file_id);
+        self.submit(Op::Statx {
+            file_id,
+            file,
+            //SAFETY: it's plain old data, so all zeroes is a valid `statx`
+            buf: Box::new(unsafe { std::mem::zeroed() }),
+            completion,
+        });
+    }
+
+    pub fn open (&self, path: &Path, flags: i32, files: Arc<SharedFiles>, completion: Completion<Result<Id, std::io::Error>>) {
+        match path_to_cstring(path) {
+            Ok(path) => self.submit(Op::Open { path, flags, files, completion }),
+            Err(e) => completion.complete(Err(e)),
+        }
+    }
+
+    pub fn rename (&self, from: &Path, to: &Path, completion: Completion<Result<(), std::io::Error>>) {
+        match (path_to_cstring(from), path_to_cstring(to)) {
+            (Ok(from), Ok(to)) => self.submit(Op::Rename { from, to, completion }),
+            (Err(e), _) | (_, Err(e)) => completion.complete(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::adapters::io_worker::IoThread;
+    use crate::adapters::io_worker::requests::{CloseFile, CreateFile, FileMetadata, OpenFile, ReadFile, Rename, WriteFile};
+    use crate::adapters::uring::{BusyFiles, Uring};
+    use crate::adapters::IoFuture;
+    use crate::executor::block_on;
+    use crate::id::IdGenerator;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn busy_files_wait_for_idle() {
+        let busy = Arc::new(BusyFiles::default());
+        let id = IdGenerator::default().next();
+        busy.mark_busy(id);
+
+        let finished = Arc::new(AtomicBool::new(false));
+        let waiter = std::thread::spawn({
+            let (busy, finished) = (busy.clone(), finished.clone());
+            move || {
+                busy.wait_for(id);
+                finished.store(true, Ordering::SeqCst);
+            }
+        });
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!finished.load(Ordering::SeqCst));
+        busy.mark_idle(id);
+        waiter.join().unwrap();
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn file_ops_through_the_ring() {
+        //without a ring everything goes through the io threads, which the other tests already cover
+        if Uring::get().is_none() {
+            return;
+        }
+
+        let dir = TempPath::new("uring");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        let chunks: Vec<Vec<u8>> = (0..64_u8).map(|i| vec![i; 100]).collect();
+
+        let (len, first_chunk) = block_on({
+            let chunks = chunks.clone();
+            async move {
+                let id = IoFuture::new(CreateFile(from.clone())).await.unwrap();
+                //nothing waits between these, so they're only kept in order by the worker waiting on the ring
+                for chunk in chunks {
+                    IoThread::get().send_detached_request(WriteFile(id, chunk));
+                }
+                let metadata = IoFuture::new(FileMetadata(id)).await.unwrap();
+                assert!(metadata.is_file());
+                assert!(metadata.modified().is_ok());
+                IoFuture::new(CloseFile(id)).await.unwrap();
+
+                IoFuture::new(Rename(from, to.clone())).await.unwrap();
+                let id = IoFuture::new(OpenFile(to)).await.unwrap();
+                let first_chunk = IoFuture::new(ReadFile { file: id, max_bytes: 100 }).await.unwrap();
+                IoFuture::new(CloseFile(id)).await.unwrap();
+
+                (metadata.len(), first_chunk)
+            }
+        });
+
+        assert_eq!(len, 6400);
+        assert_eq!(first_chunk, chunks[0]);
+        assert_eq!(std::fs::read(dir.join("to")).unwrap(), chunks.concat());
+    }
+}
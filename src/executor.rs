@@ -1,13 +1,28 @@
 use crate::executor::sealed::CanUseCannotImplement;
 use crate::id::{Id, IdGenerator};
+use crate::metrics::MetricsSnapshot;
 use task_runner::Pool;
+use watchdog::Watchdog;
 use std::any::Any;
 use std::collections::HashMap;
 use std::future::Future;
+use std::panic::Location;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 
+mod activity;
+mod builder;
+mod dump;
+mod task_info;
 mod task_runner;
 mod waker;
+mod watchdog;
+
+pub use builder::TaskBuilder;
+pub use activity::TaskState;
+pub use dump::{TaskDump, TaskDumpEntry};
+pub use task_info::{TaskInfo, TaskRegistry};
+pub use watchdog::{WatchdogConfig, WatchdogEvent, WatchdogReport};
 
 pub type Erased = Box<dyn Any + Send>;
 pub type BoxedFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
@@ -25,6 +40,8 @@ impl<T: CanUseCannotImplement> ExecutorStage for T {}
 pub struct Running {
     pool: Pool,
     id_generator: IdGenerator,
+    registry: TaskRegistry,
+    watchdog: Option<Watchdog>,
 }
 pub struct Finished;
 
@@ -35,29 +52,73 @@ pub struct Executor<Stage: ExecutorStage> {
 
 impl Executor<Running> {
     pub fn start(n_workers: usize) -> Self {
+        let registry: TaskRegistry = Arc::new(Mutex::new(HashMap::new()));
+
         Self {
             results_cache: HashMap::new(),
             stage_details: Running {
-                pool: Pool::new(n_workers),
+                pool: Pool::new(n_workers, &registry),
                 id_generator: IdGenerator::default(),
+                registry,
+                watchdog: None,
             },
         }
     }
 
+    ///starts a watchdog thread which reports slow polls and stalled tasks, replacing any existing one
+    pub fn enable_watchdog(&mut self, config: WatchdogConfig) {
+        if let Some(old) = self.stage_details.watchdog.take() {
+            old.stop();
+        }
+
+        self.stage_details.watchdog = Some(Watchdog::start(
+            config,
+            self.stage_details.pool.activities(),
+            self.stage_details.registry.clone(),
+        ));
+    }
+
+    #[track_caller]
+    pub const fn task(&mut self) -> TaskBuilder<'_> {
+        TaskBuilder::new(self, Location::caller())
+    }
+
+    #[track_caller]
     pub fn run<F: Future + Send + 'static>(&mut self, f: F) -> Id
     where
         F::Output: Send,
     {
-        let id = self.stage_details.id_generator.next();
-        self.stage_details.pool.run_future(
-            id,
-            Box::pin(async {
-                let res = f.await;
-                let erased: Erased = Box::new(res);
-                erased
-            }),
-        );
-        id
+        self.task().run(f)
+    }
+
+    #[track_caller]
+    pub fn run_named<F: Future + Send + 'static>(&mut self, name: impl Into<String>, f: F) -> Id
+    where
+        F::Output: Send,
+    {
+        self.task().name(name).run(f)
+    }
+
+    ///lists every task that has been spawned and hasn't yet finished, in no particular order
+    pub fn live_tasks(&self) -> Vec<TaskInfo> {
+        self.stage_details.registry.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn task_info(&self, id: Id) -> Option<TaskInfo> {
+        self.stage_details.registry.lock().unwrap().get(&id).cloned()
+    }
+
+    ///captures what every live task is up to, on every runner
+    pub fn task_dump(&self) -> TaskDump {
+        TaskDump::capture(&self.stage_details.pool.activities(), &self.stage_details.registry)
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            runners: self.stage_details.pool.metrics(),
+            timer_heap_size: crate::timer_future::timer_heap_size(),
+            io: crate::adapters::io_metrics(),
+        }
     }
 
     pub fn take_result<T: 'static>(&mut self, id: Id) -> FutureResult<T> {
@@ -68,6 +129,9 @@ impl Executor<Running> {
 
     pub fn join(mut self) -> Executor<Finished> {
         self.results_cache.extend(self.stage_details.pool.join());
+        if let Some(watchdog) = self.stage_details.watchdog {
+            watchdog.stop();
+        }
 
         Executor {
             results_cache: self.results_cache,
@@ -117,3 +181,14 @@ impl<T> From<FutureResult<T>> for Option<T> {
         }
     }
 }
+
+///runs a single future to completion on a fresh executor
+#[cfg(test)]
+pub fn block_on<F: Future + Send + 'static>(f: F) -> F::Output
+where
+    F::Output: Send + 'static,
+{
+    let mut executor = Executor::start(1);
+    let id = executor.run(f);
+    executor.join().take_result(id).unwrap()
+}
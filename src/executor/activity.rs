@@ -0,0 +1,97 @@
+use crate::id::Id;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TaskState {
+    ///woken, and waiting for the runner to get round to polling it
+    Scheduled,
+    ///currently being polled
+    Running,
+    ///returned `Poll::Pending` and is waiting for a wake
+    Idle,
+}
+
+impl TaskState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Scheduled => "scheduled",
+            Self::Running => "running",
+            Self::Idle => "idle",
+        }
+    }
+}
+
+impl Display for TaskState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TaskActivity {
+    pub state: TaskState,
+    pub last_woken: Instant,
+    pub last_poll: Option<Instant>,
+    pub wake_count: u64,
+    pub poll_count: u64,
+}
+
+///what a runner is doing right now, shared with its wakers, the watchdog and task dumps
+pub struct RunnerActivity {
+    pub thread_name: String,
+    pub current_poll: Mutex<Option<(Id, Instant)>>,
+    pub tasks: Mutex<HashMap<Id, TaskActivity>>,
+}
+
+impl RunnerActivity {
+    pub fn new(thread_name: String) -> Self {
+        Self {
+            thread_name,
+            current_poll: Mutex::new(None),
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn task_added(&self, id: Id) {
+        self.tasks.lock().unwrap().insert(id, TaskActivity {
+            state: TaskState::Scheduled,
+            last_woken: Instant::now(),
+            last_poll: None,
+            wake_count: 0,
+            poll_count: 0,
+        });
+    }
+
+    pub fn task_woken(&self, id: Id) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(&id) {
+            task.state = TaskState::Scheduled;
+            task.last_woken = Instant::now();
+            task.wake_count += 1;
+        }
+    }
+
+    pub fn poll_started(&self, id: Id, at: Instant) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(&id) {
+            task.state = TaskState::Running;
+            task.last_poll = Some(at);
+            task.poll_count += 1;
+        }
+        *self.current_poll.lock().unwrap() = Some((id, at));
+    }
+
+    pub fn poll_finished(&self, id: Id, ready: bool) {
+        *self.current_poll.lock().unwrap() = None;
+
+        if ready {
+            self.tasks.lock().unwrap().remove(&id);
+        } else if let Some(task) = self.tasks.lock().unwrap().get_mut(&id) {
+            //if it got woken during the poll, it's already been re-scheduled
+            if task.state == TaskState::Running {
+                task.state = TaskState::Idle;
+            }
+        }
+    }
+}
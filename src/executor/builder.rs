@@ -0,0 +1,65 @@
+use crate::executor::task_info::TaskInfo;
+use crate::executor::{Erased, Executor, Running};
+use crate::id::Id;
+use crate::debug;
+use std::future::Future;
+use std::panic::Location;
+
+#[must_use = "a task builder does nothing until `run` is called"]
+pub struct TaskBuilder<'executor> {
+    executor: &'executor mut Executor<Running>,
+    name: Option<String>,
+    location: &'static Location<'static>,
+    metadata: Vec<(String, String)>,
+}
+
+impl<'executor> TaskBuilder<'executor> {
+    pub(super) const fn new(
+        executor: &'executor mut Executor<Running>,
+        location: &'static Location<'static>,
+    ) -> Self {
+        Self {
+            executor,
+            name: None,
+            location,
+            metadata: vec![],
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn run<F: Future + Send + 'static>(self, f: F) -> Id
+    where
+        F::Output: Send,
+    {
+        let stage = &mut self.executor.stage_details;
+        let id = stage.id_generator.next();
+
+        let info = TaskInfo {
+            id,
+            name: self.name,
+            location: self.location,
+            metadata: self.metadata,
+        };
+        debug!("executor", "spawning {info}");
+        stage.registry.lock().unwrap().insert(id, info);
+
+        stage.pool.run_future(
+            id,
+            Box::pin(async {
+                let res = f.await;
+                let erased: Erased = Box::new(res);
+                erased
+            }),
+        );
+        id
+    }
+}
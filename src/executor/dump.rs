@@ -0,0 +1,178 @@
+use crate::executor::activity::{RunnerActivity, TaskState};
+use crate::executor::TaskRegistry;
+use crate::id::Id;
+use std::fmt::{Display, Formatter, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct TaskDumpEntry {
+    pub id: Id,
+    pub name: Option<String>,
+    pub location: String,
+    pub metadata: Vec<(String, String)>,
+    pub runner: String,
+    pub state: TaskState,
+    ///how long ago the task was last polled, or `None` if it never has been
+    pub last_polled: Option<Duration>,
+    pub wake_count: u64,
+    pub poll_count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskDump {
+    pub tasks: Vec<TaskDumpEntry>,
+}
+
+impl TaskDump {
+    pub fn capture(runners: &[Arc<RunnerActivity>], registry: &TaskRegistry) -> Self {
+        let now = Instant::now();
+        let mut tasks = vec![];
+
+        for runner in runners {
+            //take a copy so we don't hold both locks at once
+            let runner_tasks: Vec<_> = runner
+                .tasks
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, activity)| (*id, *activity))
+                .collect();
+
+            for (id, activity) in runner_tasks {
+                let info = registry.lock().unwrap().get(&id).cloned();
+                tasks.push(TaskDumpEntry {
+                    id,
+                    name: info.as_ref().and_then(|info| info.name.clone()),
+                    location: info.as_ref().map(|info| info.location.to_string()).unwrap_or_default(),
+                    metadata: info.map(|info| info.metadata).unwrap_or_default(),
+                    runner: runner.thread_name.clone(),
+                    state: activity.state,
+                    last_polled: activity.last_poll.map(|at| now.saturating_duration_since(at)),
+                    wake_count: activity.wake_count,
+                    poll_count: activity.poll_count,
+                });
+            }
+        }
+
+        Self { tasks }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("[");
+        for (i, task) in self.tasks.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            out.push_str("{\"id\":");
+            write_json_str(&mut out, &task.id.to_string());
+            out.push_str(",\"name\":");
+            match &task.name {
+                Some(name) => write_json_str(&mut out, name),
+                None => out.push_str("null"),
+            }
+            out.push_str(",\"location\":");
+            write_json_str(&mut out, &task.location);
+            out.push_str(",\"metadata\":{");
+            for (j, (k, v)) in task.metadata.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                write_json_str(&mut out, k);
+                out.push(':');
+                write_json_str(&mut out, v);
+            }
+            out.push_str("},\"runner\":");
+            write_json_str(&mut out, &task.runner);
+            let _ = write!(out, ",\"state\":\"{}\",\"last_polled_secs_ago\":", task.state);
+            match task.last_polled {
+                Some(ago) => {
+                    let _ = write!(out, "{}", ago.as_secs_f64());
+                }
+                None => out.push_str("null"),
+            }
+            let _ = write!(
+                out,
+                ",\"wake_count\":{},\"poll_count\":{}}}",
+                task.wake_count, task.poll_count
+            );
+        }
+        out.push(']');
+        out
+    }
+}
+
+fn write_json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl Display for TaskDump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} live task(s)", self.tasks.len())?;
+        for task in &self.tasks {
+            match &task.name {
+                Some(name) => write!(f, "  {name:?} ({})", task.id)?,
+                None => write!(f, "  {}", task.id)?,
+            }
+            write!(f, " on {}: {}", task.runner, task.state)?;
+            match task.last_polled {
+                Some(ago) => write!(f, ", last polled {ago:?} ago")?,
+                None => write!(f, ", never polled")?,
+            }
+            writeln!(
+                f,
+                ", {} wake(s), {} poll(s), spawned at {}",
+                task.wake_count, task.poll_count, task.location
+            )?;
+            for (k, v) in &task.metadata {
+                writeln!(f, "    {k} = {v}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::activity::TaskState;
+    use crate::executor::dump::{TaskDump, TaskDumpEntry};
+    use crate::id::IdGenerator;
+    use std::time::Duration;
+
+    #[test]
+    fn json_escapes_strings() {
+        let dump = TaskDump {
+            tasks: vec![TaskDumpEntry {
+                id: IdGenerator::default().next(),
+                name: Some("say \"hi\"\n".into()),
+                location: "src/main.rs:1:1".into(),
+                metadata: vec![("k".into(), "v".into())],
+                runner: "task_runner_0".into(),
+                state: TaskState::Idle,
+                last_polled: Some(Duration::from_millis(500)),
+                wake_count: 2,
+                poll_count: 3,
+            }],
+        };
+
+        assert_eq!(
+            dump.to_json(),
+            r##"[{"id":"#0.0","name":"say \"hi\"\n","location":"src/main.rs:1:1","metadata":{"k":"v"},"runner":"task_runner_0","state":"idle","last_polled_secs_ago":0.5,"wake_count":2,"poll_count":3}]"##
+        );
+    }
+}
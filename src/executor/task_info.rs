@@ -0,0 +1,34 @@
+use crate::id::Id;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+pub type TaskRegistry = Arc<Mutex<HashMap<Id, TaskInfo>>>;
+
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: Id,
+    pub name: Option<String>,
+    pub location: &'static Location<'static>,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl TaskInfo {
+    pub fn get_metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Display for TaskInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name:?} ({})", self.id)?,
+            None => write!(f, "{}", self.id)?,
+        }
+        write!(f, " spawned at {}", self.location)
+    }
+}
@@ -1,72 +1,107 @@
-use crate::executor::{BoxedFuture, Erased};
+use crate::executor::{BoxedFuture, Erased, TaskRegistry};
 use crate::id::Id;
 use crate::executor::waker::WakerData;
+use crate::executor::activity::RunnerActivity;
+use crate::metrics::{RunnerMetrics, RunnerSnapshot};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::thread::JoinHandle;
-use crate::prt;
+use std::time::Instant;
+use crate::{debug, trace, warn};
 
 pub struct TaskRunner {
     handle: JoinHandle<()>,
     task_sender: Sender<(Id, BoxedFuture<Erased>)>,
-    current_tasks: Arc<AtomicUsize>,
+    metrics: Arc<RunnerMetrics>,
+    activity: Arc<RunnerActivity>,
     needs_to_stop: Arc<AtomicBool>,
     result_receiver: Receiver<(Id, Erased)>,
 }
 
 impl TaskRunner {
-    pub fn new() -> Self {
-        let current_tasks = Arc::new(AtomicUsize::new(0));
+    pub fn new(index: usize, registry: TaskRegistry) -> Self {
+        let metrics = Arc::new(RunnerMetrics::default());
         let needs_to_stop = Arc::new(AtomicBool::new(false));
         let (task_sender, task_receiver) = channel::<(Id, BoxedFuture<Erased>)>();
         let (result_sender, result_receiver) = channel();
 
-        let thread_current_tasks = current_tasks.clone();
+        let thread_metrics = metrics.clone();
+        let thread_name = format!("task_runner_{index}");
+        let activity = Arc::new(RunnerActivity::new(thread_name.clone()));
+        let thread_activity = activity.clone();
         let thread_stop = needs_to_stop.clone();
 
-        let handle = std::thread::spawn(move || {
+        let handle = std::thread::Builder::new().name(thread_name).spawn(move || {
             let (poll_sender, poll_receiver) = channel::<Id>();
 
             let mut to_poll = HashMap::new();
             loop {
+                //checked before taking new tasks, so that anything sent before we were told to stop still gets run
+                let stopping = thread_stop.load(Ordering::SeqCst);
+
                 for (id, fut) in task_receiver.try_iter() {
                     to_poll.insert(id, fut);
+                    thread_activity.task_added(id);
                     poll_sender.send(id).unwrap();
                 }
 
-                if thread_stop.load(Ordering::SeqCst) && to_poll.is_empty() {
+                if stopping && to_poll.is_empty() {
                     break;
                 }
 
                 for id in poll_receiver.try_iter() {
                     match to_poll.entry(id) {
                         Entry::Occupied(mut occ) => {
-                            let waker = WakerData::new_waker(poll_sender.clone(), id);
+                            let waker = WakerData::new_waker(
+                                poll_sender.clone(),
+                                id,
+                                thread_metrics.clone(),
+                                thread_activity.clone(),
+                            );
                             let mut cx = Context::from_waker(&waker);
 
-                            if let Poll::Ready(res) = occ.get_mut().as_mut().poll(&mut cx) {
+                            trace!("task runner", "polling task {id}");
+                            let poll_start = Instant::now();
+                            thread_activity.poll_started(id, poll_start);
+                            let poll = occ.get_mut().as_mut().poll(&mut cx);
+                            thread_activity.poll_finished(id, poll.is_ready());
+                            thread_metrics.poll_duration.record(poll_start.elapsed());
+                            thread_metrics.polls.fetch_add(1, Ordering::Relaxed);
+
+                            if let Poll::Ready(res) = poll {
+                                debug!("task runner", "task {id} finished");
                                 let _ = result_sender.send((id, res));
                                 drop(occ.remove()); //we don't need to poll this future lol
-                                thread_current_tasks.fetch_sub(1, Ordering::Relaxed);
+                                registry.lock().unwrap().remove(&id);
+                                thread_metrics.tasks.fetch_sub(1, Ordering::Relaxed);
+                                thread_metrics.completed.fetch_add(1, Ordering::Relaxed);
                             }
                         }
                         Entry::Vacant(_) => {
-                            eprintln!("[task runner] tried to poll non-existent task");
+                            warn!("task runner", "tried to poll non-existent task {id}");
                         }
                     }
                 }
             }
-        });
+        }).expect("unable to spawn task runner thread");
 
-        Self { handle, task_sender, current_tasks, needs_to_stop, result_receiver }
+        Self { handle, task_sender, metrics, activity, needs_to_stop, result_receiver }
     }
 
     pub fn current_number_of_tasks(&self) -> usize {
-        self.current_tasks.load(Ordering::Relaxed)
+        self.metrics.tasks.load(Ordering::Relaxed)
+    }
+
+    pub fn metrics(&self, index: usize) -> RunnerSnapshot {
+        self.metrics.snapshot(index)
+    }
+
+    pub fn activity(&self) -> Arc<RunnerActivity> {
+        self.activity.clone()
     }
 
     pub fn take_results(&self) -> impl Iterator<Item = (Id, Erased)> + use<'_> {
@@ -74,7 +109,8 @@ impl TaskRunner {
     }
 
     pub fn send_task(&self, id: Id, fut: BoxedFuture<Erased>) {
-        self.current_tasks.fetch_add(1, Ordering::Relaxed);
+        self.metrics.tasks.fetch_add(1, Ordering::Relaxed);
+        self.metrics.spawned.fetch_add(1, Ordering::Relaxed);
         self.task_sender.send((id, fut)).unwrap();
     }
 
@@ -90,9 +126,9 @@ pub struct Pool {
 }
 
 impl Pool {
-    pub fn new(n_workers: usize) -> Self {
+    pub fn new(n_workers: usize, registry: &TaskRegistry) -> Self {
         Self {
-            runners: (0..n_workers).map(|_| TaskRunner::new()).collect(),
+            runners: (0..n_workers).map(|i| TaskRunner::new(i, registry.clone())).collect(),
         }
     }
 
@@ -104,10 +140,22 @@ impl Pool {
             .map(|(i, runner)| (i, runner.current_number_of_tasks()))
             .min_by_key(|(_, n)| *n)
             .unwrap();
-        prt!("[pool] sending task to {runner_index}");
+        debug!("pool", "sending task {id} to {runner_index}");
         self.runners[runner_index].send_task(id, f);
     }
 
+    pub fn metrics(&self) -> Vec<RunnerSnapshot> {
+        self.runners
+            .iter()
+            .enumerate()
+            .map(|(i, runner)| runner.metrics(i))
+            .collect()
+    }
+
+    pub fn activities(&self) -> Vec<Arc<RunnerActivity>> {
+        self.runners.iter().map(TaskRunner::activity).collect()
+    }
+
     pub fn collect_results(&self) -> impl Iterator<Item = (Id, Erased)> + use<'_> {
         self.runners
             .iter()
@@ -1,24 +1,37 @@
+use crate::executor::activity::RunnerActivity;
 use crate::id::Id;
-use crate::prt;
+use crate::metrics::RunnerMetrics;
+use crate::trace;
 use std::{
     sync::mpsc::Sender,
     task::{RawWaker, RawWakerVTable},
 };
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::task::Waker;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct WakerData {
     poll_me: Sender<Id>,
     id: Id,
+    metrics: Arc<RunnerMetrics>,
+    activity: Arc<RunnerActivity>,
 }
 
 
 impl WakerData {
-    pub fn new_waker(poll_me: Sender<Id>, id: Id) -> Waker {
-        prt!("[wakerdata] create new {id:?}");
+    pub fn new_waker(
+        poll_me: Sender<Id>,
+        id: Id,
+        metrics: Arc<RunnerMetrics>,
+        activity: Arc<RunnerActivity>,
+    ) -> Waker {
+        trace!("waker", "create new {id}");
         let waker_data = Box::new(Self {
             poll_me,
-            id
+            id,
+            metrics,
+            activity,
         });
         let waker_data = Box::into_raw(waker_data);
 
@@ -31,7 +44,7 @@ const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, dro
 
 unsafe fn clone(data: *const ()) -> RawWaker {
     let old_data = &*(data.cast::<WakerData>());
-    prt!("[vtable] clone: {:?}", old_data.id);
+    trace!("waker", "clone {}", old_data.id);
 
     let new_data = Box::new(old_data.clone());
 
@@ -40,7 +53,9 @@ unsafe fn clone(data: *const ()) -> RawWaker {
 
 unsafe fn wake(data: *const ()) {
     let data = Box::from_raw(data as *mut WakerData);
-    prt!("[vtable] wake owned {:?}", data.id);
+    trace!("waker", "wake owned {}", data.id);
+    data.metrics.wakes.fetch_add(1, Ordering::Relaxed);
+    data.activity.task_woken(data.id);
     data.poll_me
         .send(data.id)
         .expect("unable to send task id to executor");
@@ -48,7 +63,9 @@ unsafe fn wake(data: *const ()) {
 
 unsafe fn wake_by_ref(data: *const ()) {
     let data = &*(data.cast::<WakerData>());
-    prt!("[vtable] wake reference {:?}", data.id);
+    trace!("waker", "wake reference {}", data.id);
+    data.metrics.wakes.fetch_add(1, Ordering::Relaxed);
+    data.activity.task_woken(data.id);
     data.poll_me
         .send(data.id)
         .expect("unable to send task id to executor");
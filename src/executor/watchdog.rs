@@ -0,0 +1,152 @@
+use crate::executor::activity::RunnerActivity;
+use crate::executor::TaskRegistry;
+use crate::id::Id;
+use crate::warn;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub struct WatchdogConfig {
+    ///flag any single poll which takes longer than this
+    pub slow_poll_threshold: Duration,
+    ///flag any task which hasn't been woken for this long
+    pub stall_threshold: Duration,
+    ///how often the watchdog checks the runners - polls shorter than this can slip through
+    pub check_interval: Duration,
+    pub reporter: Box<dyn Fn(&WatchdogReport) + Send>,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            slow_poll_threshold: Duration::from_millis(100),
+            stall_threshold: Duration::from_secs(10),
+            check_interval: Duration::from_millis(25),
+            reporter: Box::new(|report| warn!("watchdog", "{report}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WatchdogEvent {
+    SlowPoll { running_for: Duration },
+    Stalled { idle_for: Duration },
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchdogReport {
+    pub task: Id,
+    pub name: Option<String>,
+    pub runner: String,
+    pub event: WatchdogEvent,
+}
+
+impl Display for WatchdogReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "task {name:?} ({})", self.task)?,
+            None => write!(f, "task {}", self.task)?,
+        }
+        match self.event {
+            WatchdogEvent::SlowPoll { running_for } => write!(
+                f,
+                " has been blocking {} in a single poll for {running_for:?}",
+                self.runner
+            ),
+            WatchdogEvent::Stalled { idle_for } => write!(
+                f,
+                " on {} hasn't been woken for {idle_for:?}",
+                self.runner
+            ),
+        }
+    }
+}
+
+pub struct Watchdog {
+    handle: JoinHandle<()>,
+    needs_to_stop: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    pub fn start(
+        config: WatchdogConfig,
+        runners: Vec<Arc<RunnerActivity>>,
+        registry: TaskRegistry,
+    ) -> Self {
+        let needs_to_stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = needs_to_stop.clone();
+
+        let handle = std::thread::Builder::new()
+            .name("watchdog".into())
+            .spawn(move || {
+                //only report each poll/each quiet period once
+                let mut reported_polls = HashSet::new();
+                let mut reported_stalls = HashSet::new();
+
+                while !thread_stop.load(Ordering::SeqCst) {
+                    std::thread::sleep(config.check_interval);
+
+                    let name_of = |id: Id| {
+                        registry
+                            .lock()
+                            .unwrap()
+                            .get(&id)
+                            .and_then(|info| info.name.clone())
+                    };
+
+                    let mut seen_polls = HashSet::new();
+                    let mut seen_stalls = HashSet::new();
+
+                    for runner in &runners {
+                        let current_poll = *runner.current_poll.lock().unwrap();
+                        if let Some((id, started)) = current_poll {
+                            seen_polls.insert((id, started));
+                            let running_for = started.elapsed();
+                            if running_for > config.slow_poll_threshold && reported_polls.insert((id, started)) {
+                                (config.reporter)(&WatchdogReport {
+                                    task: id,
+                                    name: name_of(id),
+                                    runner: runner.thread_name.clone(),
+                                    event: WatchdogEvent::SlowPoll { running_for },
+                                });
+                            }
+                        }
+
+                        let stalled: Vec<(Id, Instant)> = runner
+                            .tasks
+                            .lock()
+                            .unwrap()
+                            .iter()
+                            .filter(|(_, task)| task.last_woken.elapsed() > config.stall_threshold)
+                            .map(|(id, task)| (*id, task.last_woken))
+                            .collect();
+                        for (id, woken) in stalled {
+                            seen_stalls.insert((id, woken));
+                            if reported_stalls.insert((id, woken)) {
+                                (config.reporter)(&WatchdogReport {
+                                    task: id,
+                                    name: name_of(id),
+                                    runner: runner.thread_name.clone(),
+                                    event: WatchdogEvent::Stalled { idle_for: woken.elapsed() },
+                                });
+                            }
+                        }
+                    }
+
+                    reported_polls.retain(|poll| seen_polls.contains(poll));
+                    reported_stalls.retain(|stall| seen_stalls.contains(stall));
+                }
+            })
+            .expect("unable to spawn watchdog thread");
+
+        Self { handle, needs_to_stop }
+    }
+
+    pub fn stop(self) {
+        self.needs_to_stop.store(true, Ordering::SeqCst);
+        self.handle.join().unwrap();
+    }
+}
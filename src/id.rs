@@ -30,4 +30,10 @@ impl IdGenerator {
 pub struct Id {
     index: usize,
     generation: usize,
-}
\ No newline at end of file
+}
+
+impl std::fmt::Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}.{}", self.index, self.generation)
+    }
+}
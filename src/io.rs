@@ -0,0 +1,465 @@
+use std::future::Future;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::stream::Stream;
+
+mod buffered;
+pub use buffered::{BufReader, BufWriter};
+
+///something bytes can be read from without blocking - if nothing is available yet, `poll_read` arranges for the task to
+///be woken once there might be
+pub trait AsyncRead {
+    ///reads into `buf`, giving how many bytes were read. `Ok(0)` means the end of the stream, unless `buf` was empty.
+    fn poll_read (self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, std::io::Error>>;
+}
+
+///something bytes can be written to without blocking
+pub trait AsyncWrite {
+    ///writes some of `buf`, giving how many bytes were written
+    fn poll_write (self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>>;
+
+    ///makes sure anything buffered along the way has been written out
+    fn poll_flush (self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>>;
+}
+
+impl<T: AsyncRead + Unpin + ?Sized> AsyncRead for &mut T {
+    fn poll_read (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut T {
+    fn poll_write (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_flush (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+}
+
+impl<T: AsyncRead + Unpin + ?Sized> AsyncRead for Box<T> {
+    fn poll_read (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for Box<T> {
+    fn poll_write (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_flush (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+}
+
+///a reader with an internal buffer, which can be looked at directly
+pub trait AsyncBufRead: AsyncRead {
+    ///the buffered bytes, reading more from the underlying reader first if there aren't any. An empty slice means the
+    ///end of the stream.
+    fn poll_fill_buf (self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8], std::io::Error>>;
+
+    ///marks `amt` bytes from the start of the buffer as read
+    fn consume (self: Pin<&mut Self>, amt: usize);
+}
+
+impl<T: AsyncBufRead + Unpin + ?Sized> AsyncBufRead for &mut T {
+    fn poll_fill_buf (self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8], std::io::Error>> {
+        Pin::new(&mut **self.get_mut()).poll_fill_buf(cx)
+    }
+
+    fn consume (mut self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut **self).consume(amt);
+    }
+}
+
+///how much `read_to_end` tries to read at once
+const READ_TO_END_CHUNK: usize = 4096;
+
+pub trait AsyncReadExt: AsyncRead + Unpin {
+    fn read<'a> (&'a mut self, buf: &'a mut [u8]) -> Read<'a, Self> {
+        Read {
+            reader: self,
+            buf,
+        }
+    }
+
+    ///fills all of `buf`, failing with [`ErrorKind::UnexpectedEof`] if the stream ends first
+    fn read_exact<'a> (&'a mut self, buf: &'a mut [u8]) -> ReadExact<'a, Self> {
+        ReadExact {
+            reader: self,
+            buf,
+            filled: 0,
+        }
+    }
+
+    ///reads everything left onto the end of `buf`, giving how many bytes that was
+    fn read_to_end<'a> (&'a mut self, buf: &'a mut Vec<u8>) -> ReadToEnd<'a, Self> {
+        let start_len = buf.len();
+        ReadToEnd {
+            reader: self,
+            buf,
+            start_len,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncReadExt for R {}
+
+pub trait AsyncWriteExt: AsyncWrite + Unpin {
+    fn write<'a> (&'a mut self, buf: &'a [u8]) -> Write<'a, Self> {
+        Write {
+            writer: self,
+            buf,
+        }
+    }
+
+    fn write_all<'a> (&'a mut self, buf: &'a [u8]) -> WriteAll<'a, Self> {
+        WriteAll {
+            writer: self,
+            buf,
+        }
+    }
+
+    fn flush (&mut self) -> Flush<'_, Self> {
+        Flush {
+            writer: self,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> AsyncWriteExt for W {}
+
+pub trait AsyncBufReadExt: AsyncBufRead + Unpin {
+    ///reads onto the end of `buf` up to and including `delimiter`, or until the end of the stream. Gives how many bytes
+    ///were read, so `Ok(0)` means the stream had already ended.
+    fn read_until<'a> (&'a mut self, delimiter: u8, buf: &'a mut Vec<u8>) -> ReadUntil<'a, Self> {
+        ReadUntil {
+            reader: self,
+            delimiter,
+            buf,
+            read: 0,
+        }
+    }
+
+    ///reads a line onto the end of `line`, keeping the newline. Fails with [`ErrorKind::InvalidData`] if the line isn't
+    ///UTF-8, in which case `line` is left as it was.
+    fn read_line<'a> (&'a mut self, line: &'a mut String) -> ReadLine<'a, Self> {
+        ReadLine {
+            reader: self,
+            line,
+            bytes: vec![],
+        }
+    }
+
+    ///a stream of the lines left, without their line endings
+    fn lines (self) -> Lines<Self> where Self: Sized {
+        Lines {
+            reader: self,
+            bytes: vec![],
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> AsyncBufReadExt for R {}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct Read<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for Read<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Read { reader, buf } = &mut *self;
+        Pin::new(&mut **reader).poll_read(cx, buf)
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadExact<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+    filled: usize,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadExact<'_, R> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadExact { reader, buf, filled } = &mut *self;
+
+        while *filled < buf.len() {
+            match Pin::new(&mut **reader).poll_read(cx, &mut buf[*filled..]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::UnexpectedEof.into())),
+                Poll::Ready(Ok(n)) => *filled += n,
+                Poll::Ready(Err(e)) if e.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadToEnd<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut Vec<u8>,
+    start_len: usize,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadToEnd<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadToEnd { reader, buf, start_len } = &mut *self;
+
+        loop {
+            let len = buf.len();
+            buf.resize(len + READ_TO_END_CHUNK, 0);
+            let res = Pin::new(&mut **reader).poll_read(cx, &mut buf[len..]);
+            buf.truncate(len + match res {
+                Poll::Ready(Ok(n)) => n,
+                _ => 0,
+            });
+
+            match res {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => return Poll::Ready(Ok(buf.len() - *start_len)),
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) if e.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct Write<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Write<'_, W> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Write { writer, buf } = &mut *self;
+        Pin::new(&mut **writer).poll_write(cx, buf)
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteAll<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteAll<'_, W> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let WriteAll { writer, buf } = &mut *self;
+
+        while !buf.is_empty() {
+            match Pin::new(&mut **writer).poll_write(cx, buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => *buf = &buf[n..],
+                Poll::Ready(Err(e)) if e.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct Flush<'a, W: ?Sized> {
+    writer: &'a mut W,
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Flush<'_, W> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.writer).poll_flush(cx)
+    }
+}
+
+fn invalid_line () -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, "line wasn't valid UTF-8")
+}
+
+fn poll_read_until<R: AsyncBufRead + Unpin + ?Sized> (
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    delimiter: u8,
+    buf: &mut Vec<u8>,
+    read: &mut usize,
+) -> Poll<Result<usize, std::io::Error>> {
+    loop {
+        let (done, used) = {
+            let available = match Pin::new(&mut *reader).poll_fill_buf(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) if e.kind() == ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(available)) => available,
+            };
+
+            if let Some(i) = available.iter().position(|&b| b == delimiter) {
+                buf.extend_from_slice(&available[..=i]);
+                (true, i + 1)
+            } else {
+                buf.extend_from_slice(available);
+                (available.is_empty(), available.len())
+            }
+        };
+
+        Pin::new(&mut *reader).consume(used);
+        *read += used;
+        if done {
+            return Poll::Ready(Ok(std::mem::take(read)));
+        }
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadUntil<'a, R: ?Sized> {
+    reader: &'a mut R,
+    delimiter: u8,
+    buf: &'a mut Vec<u8>,
+    read: usize,
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> Future for ReadUntil<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntil { reader, delimiter, buf, read } = &mut *self;
+        poll_read_until(*reader, cx, *delimiter, buf, read)
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadLine<'a, R: ?Sized> {
+    reader: &'a mut R,
+    line: &'a mut String,
+    ///the line so far, which can't go into `line` until we know it's all valid UTF-8
+    bytes: Vec<u8>,
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> Future for ReadLine<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadLine { reader, line, bytes } = &mut *self;
+
+        match poll_read_until(*reader, cx, b'\n', bytes, &mut 0) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Ready(Ok(_)) => {
+                let bytes = std::mem::take(bytes);
+                let n = bytes.len();
+                Poll::Ready(String::from_utf8(bytes).map(|s| {
+                    line.push_str(&s);
+                    n
+                }).map_err(|_| invalid_line()))
+            }
+        }
+    }
+}
+
+///the lines of a reader, from [`AsyncBufReadExt::lines`]
+#[must_use = "streams do nothing unless polled"]
+pub struct Lines<R> {
+    reader: R,
+    bytes: Vec<u8>,
+}
+
+impl<R> Lines<R> {
+    pub fn into_inner (self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for Lines<R> {
+    type Item = Result<String, std::io::Error>;
+
+    fn poll_next (mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self { reader, bytes } = &mut *self;
+
+        let n = match poll_read_until(reader, cx, b'\n', bytes, &mut 0) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+            Poll::Ready(Ok(n)) => n,
+        };
+        if n == 0 && bytes.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        let mut bytes = std::mem::take(bytes);
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+        }
+
+        Poll::Ready(Some(String::from_utf8(bytes).map_err(|_| invalid_line())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapters::file::File;
+    use crate::executor::block_on;
+    use crate::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+    use crate::stream::StreamExt;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn buffered_lines() {
+        let path = TempPath::new("buffered");
+
+        let lines = block_on({
+            let path = path.to_path_buf();
+            async move {
+                let mut writer = BufWriter::with_capacity(4, File::create(&path).await.unwrap());
+                for part in ["fir", "st\r\nsec", "ond\n", "a,b,c"] {
+                    writer.write_all(part.as_bytes()).await.unwrap();
+                }
+                writer.flush().await.unwrap();
+                writer.into_inner().close().await.unwrap();
+
+                //a capacity small enough that every line spans several fills
+                let mut reader = BufReader::with_capacity(3, File::open(&path).await.unwrap());
+                let mut first = String::new();
+                assert_eq!(reader.read_line(&mut first).await.unwrap(), 7);
+                assert_eq!(first, "first\r\n");
+
+                let mut lines = reader.lines();
+                let second = lines.next().await.unwrap().unwrap();
+
+                let mut reader = lines.into_inner();
+                let mut field = vec![];
+                reader.read_until(b',', &mut field).await.unwrap();
+                assert_eq!(field, b"a,");
+
+                let mut lines = reader.lines();
+                let rest = lines.next().await.unwrap().unwrap();
+                assert!(lines.next().await.is_none());
+                vec![second, rest]
+            }
+        });
+
+        assert_eq!(lines, ["second", "b,c"]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+///reads from `inner` in chunks of up to `capacity`, so small reads don't each go all the way to the file or socket
+pub struct BufReader<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    ///the part of `buf` which has been filled but not consumed yet
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: AsyncRead + Unpin> BufReader<R> {
+    pub fn new (inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    pub fn with_capacity (capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity.max(1)].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    pub const fn get_ref (&self) -> &R {
+        &self.inner
+    }
+
+    ///anything already buffered is lost
+    pub fn into_inner (self) -> R {
+        self.inner
+    }
+
+    ///what's been read from `inner` but not handed out yet
+    pub fn buffer (&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    pub const fn capacity (&self) -> usize {
+        self.buf.len()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for BufReader<R> {
+    fn poll_read (mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, std::io::Error>> {
+        //nothing buffered, and the read is big enough that buffering wouldn't save anything
+        let capacity = self.capacity();
+        if self.pos == self.filled && buf.len() >= capacity {
+            return Pin::new(&mut self.inner).poll_read(cx, buf);
+        }
+
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Ready(Ok(available)) => available,
+        };
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufRead for BufReader<R> {
+    fn poll_fill_buf (self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8], std::io::Error>> {
+        let this = self.get_mut();
+        if this.pos == this.filled {
+            match Pin::new(&mut this.inner).poll_read(cx, &mut this.buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(n)) => {
+                    this.pos = 0;
+                    this.filled = n;
+                }
+            }
+        }
+        Poll::Ready(Ok(&this.buf[this.pos..this.filled]))
+    }
+
+    fn consume (mut self: Pin<&mut Self>, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+///collects small writes into chunks of up to `capacity` before passing them on to `inner`. Anything still buffered
+///when it's dropped is lost, so call [`flush`](crate::io::AsyncWriteExt::flush) first.
+pub struct BufWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+    ///how much of `buf` has been written out by a flush which hasn't finished yet
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> BufWriter<W> {
+    pub fn new (inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    pub fn with_capacity (capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity.max(1)),
+            written: 0,
+        }
+    }
+
+    pub const fn get_ref (&self) -> &W {
+        &self.inner
+    }
+
+    ///anything still buffered is lost - flush first
+    pub fn into_inner (self) -> W {
+        self.inner
+    }
+
+    pub fn buffer (&self) -> &[u8] {
+        &self.buf[self.written..]
+    }
+
+    pub const fn capacity (&self) -> usize {
+        self.buf.capacity()
+    }
+
+    ///writes out everything buffered, without flushing `inner`
+    fn poll_flush_buf (&mut self, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        while self.written < self.buf.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.buf[self.written..]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => self.written += n,
+                Poll::Ready(Err(e)) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        self.buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufWriter<W> {
+    fn poll_write (self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        if this.buf.len() + buf.len() > this.buf.capacity() {
+            match this.poll_flush_buf(cx) {
+                Poll::Ready(Ok(())) => {}
+                other => return other.map(|res| res.map(|()| 0)),
+            }
+        }
+
+        if buf.len() >= this.buf.capacity() {
+            Pin::new(&mut this.inner).poll_write(cx, buf)
+        } else {
+            this.buf.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+    }
+
+    fn poll_flush (self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        let this = self.get_mut();
+        match this.poll_flush_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::{Arguments, Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub const LOG_ENV_VAR: &str = "ASYNC_EXECUTOR_LOG";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        }
+    }
+
+    const fn from_u8(n: u8) -> Option<Self> {
+        match n {
+            1 => Some(Self::Error),
+            2 => Some(Self::Warn),
+            3 => Some(Self::Info),
+            4 => Some(Self::Debug),
+            5 => Some(Self::Trace),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownLevel(pub String);
+
+impl Display for UnknownLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown log level {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLevel {}
+
+impl FromStr for Level {
+    type Err = UnknownLevel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warn" | "warning" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(UnknownLevel(s.to_string())),
+        }
+    }
+}
+
+const OFF: u8 = 0;
+const UNINITIALISED: u8 = u8::MAX;
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(UNINITIALISED);
+
+///with a facade enabled, let it do the filtering unless told otherwise
+const DEFAULT_LEVEL: Level = if cfg!(any(feature = "log", feature = "tracing")) {
+    Level::Trace
+} else {
+    Level::Warn
+};
+
+fn level_from_env() -> u8 {
+    match std::env::var(LOG_ENV_VAR) {
+        Ok(var) if var.trim().eq_ignore_ascii_case("off") => OFF,
+        Ok(var) => var.parse::<Level>().unwrap_or_else(|e| {
+            eprintln!("[WARN logging] {e} in {LOG_ENV_VAR}, using {DEFAULT_LEVEL}");
+            DEFAULT_LEVEL
+        }) as u8,
+        Err(_) => DEFAULT_LEVEL as u8,
+    }
+}
+
+///the most verbose level which will be logged, or `None` if logging is off
+pub fn max_level() -> Option<Level> {
+    let mut level = MAX_LEVEL.load(Ordering::Relaxed);
+    if level == UNINITIALISED {
+        level = level_from_env();
+        //if someone called `set_max_level` in the meantime, theirs wins
+        level = match MAX_LEVEL.compare_exchange(UNINITIALISED, level, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => level,
+            Err(set_elsewhere) => set_elsewhere,
+        };
+    }
+    Level::from_u8(level)
+}
+
+///overrides whatever was set in [`LOG_ENV_VAR`]
+pub fn set_max_level(level: Option<Level>) {
+    MAX_LEVEL.store(level.map_or(OFF, |l| l as u8), Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    max_level().is_some_and(|max| level <= max)
+}
+
+#[cfg(not(any(feature = "log", feature = "tracing")))]
+pub fn log(level: Level, target: &str, args: Arguments<'_>) {
+    eprintln!("[{level} {target}] {args}");
+}
+
+#[cfg(feature = "log")]
+pub fn log(level: Level, target: &str, args: Arguments<'_>) {
+    let level = match level {
+        Level::Error => ::log::Level::Error,
+        Level::Warn => ::log::Level::Warn,
+        Level::Info => ::log::Level::Info,
+        Level::Debug => ::log::Level::Debug,
+        Level::Trace => ::log::Level::Trace,
+    };
+    ::log::log!(target: &format!("async_executor::{target}"), level, "{args}");
+}
+
+#[cfg(all(feature = "tracing", not(feature = "log")))]
+pub fn log(level: Level, target: &str, args: Arguments<'_>) {
+    //tracing needs its levels and targets to be constant, so the subsystem goes in a field
+    match level {
+        Level::Error => ::tracing::error!(target: "async_executor", subsystem = target, "{args}"),
+        Level::Warn => ::tracing::warn!(target: "async_executor", subsystem = target, "{args}"),
+        Level::Info => ::tracing::info!(target: "async_executor", subsystem = target, "{args}"),
+        Level::Debug => ::tracing::debug!(target: "async_executor", subsystem = target, "{args}"),
+        Level::Trace => ::tracing::trace!(target: "async_executor", subsystem = target, "{args}"),
+    }
+}
+
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $target:expr, $($arg:tt)*) => {{
+        let level = $level;
+        if $crate::logging::enabled(level) {
+            $crate::logging::log(level, $target, format_args!($($arg)*));
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($target:expr, $($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Error, $target, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($target:expr, $($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Warn, $target, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($target:expr, $($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Info, $target, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($target:expr, $($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Debug, $target, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($target:expr, $($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Trace, $target, $($arg)*) };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logging::Level;
+
+    #[test]
+    fn parse_levels() {
+        assert_eq!("debug".parse::<Level>().unwrap(), Level::Debug);
+        assert_eq!(" WARNING ".parse::<Level>().unwrap(), Level::Warn);
+        assert!("loud".parse::<Level>().is_err());
+        assert!(Level::Error < Level::Trace);
+    }
+}
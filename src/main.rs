@@ -2,24 +2,24 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
 use crate::timer_future::{sleep_micros, sleep_millis};
-use crate::executor::Executor;
+use crate::executor::{Executor, Running, TaskDumpEntry, TaskState, WatchdogConfig, WatchdogEvent, WatchdogReport};
 use std::time::{Duration, Instant};
 use crate::adapters::file::File;
 use adapters::net::fully_read_from_socket;
+use crate::adapters::fs;
+use crate::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use crate::stream::StreamExt;
 
 mod timer_future;
 mod executor;
 mod id;
 mod adapters;
-
-#[macro_export]
-macro_rules! prt {
-    ($($arg:tt)*) => {{
-        if false {
-            println!($($arg)*)
-        }
-    }};
-}
+mod metrics;
+mod logging;
+mod io;
+mod stream;
+#[cfg(test)]
+mod test_util;
 
 fn timer_bits () {
     #[allow(clippy::unused_async)]
@@ -45,6 +45,14 @@ fn timer_bits () {
     }
 
     let mut executor = Executor::start(16);
+    //the blocking task is slow on purpose, so only mention anything else
+    executor.enable_watchdog(WatchdogConfig {
+        reporter: Box::new(|report: &WatchdogReport| match (&report.event, report.name.as_deref()) {
+            (WatchdogEvent::SlowPoll { .. }, Some("blocking")) => {}
+            _ => println!("[watchdog] {report}"),
+        }),
+        ..WatchdogConfig::default()
+    });
 
     let create_task = |time, local_id| async move {
         let fut = sleep_millis(time);
@@ -66,7 +74,7 @@ fn timer_bits () {
     let id4 = executor.run(create_task(200, 4));
 
     let st = executor.run(check_string("Hello, World!"));
-    let fib = executor.run(blocking_slow_future(18));
+    let fib = executor.run_named("blocking", blocking_slow_future(18));
 
     println!("[main] created all tasks, joining executor");
     let mut executor = executor.join();
@@ -90,6 +98,20 @@ fn timer_bits () {
     println!("[main] checked string is {st:?}");
 }
 
+fn sleep_and_dump (executor: &Executor<Running>) {
+    std::thread::sleep(Duration::from_millis(250));
+    let dump = executor.task_dump();
+    print!("[main] {dump}");
+    print_busy_tasks(&dump.tasks);
+}
+
+///picks out the tasks which were being polled as the dump was taken - if one keeps turning up, it's probably blocking
+fn print_busy_tasks (tasks: &[TaskDumpEntry]) {
+    for task in tasks.iter().filter(|task| task.state == TaskState::Running) {
+        println!("[main] {} was running on {}", task.name.as_deref().unwrap_or("an unnamed task"), task.runner);
+    }
+}
+
 fn tcp_bits () {
     let mut executor = Executor::start(1);
 
@@ -108,13 +130,22 @@ fn tcp_bits () {
     };
 
 
-    executor.run(printer_boi(1000, 100));
-    executor.run(streamer);
+    executor.run_named("printer", printer_boi(1000, 100));
+    executor.task()
+        .name("accept-loop")
+        .metadata("addr", "0.0.0.0:8080")
+        .run(streamer);
+
+    for task in executor.live_tasks() {
+        println!("[main] running {task}");
+    }
+    sleep_and_dump(&executor);
 
     executor.join();
 }
 
 fn file_bits () {
+    adapters::set_io_workers(2);
     let mut executor = Executor::start(1);
 
     let create_timer_task = |time, local_id| async move {
@@ -135,21 +166,13 @@ fn file_bits () {
     
     let file_open_and_read = async move {
         println!("[task file] opening file");
-        let mut file = File::open("Cargo.toml").await.expect("unable to open file");
-        println!("[task file] opened file, reading bytes");
+        let file = File::open("Cargo.toml").await.expect("unable to open file");
+        println!("[task file] opened file, reading lines");
 
-        let mut contents = vec![];
-        loop {
-            let this_round = file.read(1).await.expect("error reading");
-            if this_round.is_empty() {
-                break;
-            }
-            
-            contents.extend_from_slice(&this_round);
+        let mut lines = BufReader::new(file).lines();
+        while let Some(line) = lines.next().await {
+            println!("[task file] read {:?}", line.expect("error reading"));
         }
-
-        let file_contents_as_string = String::from_utf8(contents).expect("invalid UTF-8");
-        println!("[task file] read {file_contents_as_string:?}");
     };
 
 
@@ -161,6 +184,84 @@ fn file_bits () {
     executor.join();
 }
 
+fn fs_bits () {
+    let mut executor = Executor::start(1);
+    let dir = std::env::temp_dir().join(format!("async_executor_demo_{}", std::process::id()));
+
+    executor.run(async move {
+        fs::create_dir_all(&dir).await.expect("unable to create demo directory");
+
+        //lots of little writes, which only go to the io thread once the buffer fills up
+        let squares = File::create(dir.join("squares")).await.expect("unable to create file");
+        let mut squares = BufWriter::new(squares);
+        for i in 0..1000_u64 {
+            squares.write_all(format!("{}\n", i * i).as_bytes()).await.expect("error writing");
+        }
+        squares.flush().await.expect("error flushing");
+        println!("[task fs] wrote squares");
+
+        #[cfg(unix)]
+        {
+            let squares = File::open(dir.join("squares")).await.expect("unable to open file");
+            //SAFETY: nothing else knows about the demo directory, so nothing changes the file while it's mapped
+            let squares = unsafe { squares.map_readonly() }.await.expect("unable to map file");
+            println!("[task fs] mapped {} squares", squares.split(|&b| b == b'\n').filter(|line| !line.is_empty()).count());
+        }
+
+        //anyone reading the config sees either all of the old one or all of the new one, never a mix
+        fs::write_atomic(dir.join("config"), "threads = 1\n").await.expect("error writing config");
+        let config = fs::AtomicFile::create(dir.join("config")).await.expect("unable to create config");
+        let mut config = BufWriter::new(config);
+        for (key, value) in [("threads", 4), ("io_workers", 2)] {
+            config.write_all(format!("{key} = {value}\n").as_bytes()).await.expect("error writing config");
+        }
+        config.flush().await.expect("error flushing config");
+        config.into_inner().commit().await.expect("unable to replace config");
+        let config = fs::read_to_string(dir.join("config")).await.expect("unable to read config");
+        println!("[task fs] replaced config with {config:?}");
+
+        let mut entries = fs::read_dir(&dir).await.expect("unable to read demo directory");
+        while let Some(entry) = entries.next().await {
+            let entry = entry.expect("error reading demo directory");
+            let len = entry.metadata().await.expect("unable to stat entry").len();
+            println!("[task fs] found {}, {len} bytes", entry.file_name().display());
+        }
+
+        let mut sources = fs::walk_dir("src").include("*.rs").sorted(true);
+        while let Some(source) = sources.next().await {
+            match source {
+                Ok(source) => println!("[task fs] {}source {}", "  ".repeat(source.depth() - 1), source.path().display()),
+                Err(e) => println!("[task fs] couldn't read {}: {}", e.path().display(), e.io_error()),
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let mut changes = fs::watch(&dir, false).await.expect("unable to watch demo directory");
+            fs::write(dir.join("notes"), "hello").await.expect("error writing");
+            fs::remove_file(dir.join("notes")).await.expect("unable to remove file");
+            print_changes_until_removed(&mut changes).await;
+        }
+
+        fs::remove_dir_all(&dir).await.expect("unable to remove demo directory");
+    });
+
+    executor.join();
+}
+
+#[cfg(target_os = "linux")]
+async fn print_changes_until_removed (changes: &mut fs::Watcher) {
+    while let Some(change) = changes.next().await {
+        match change.expect("error watching demo directory") {
+            fs::WatchEvent::Removed(path) => {
+                println!("[task fs] saw {} removed", path.display());
+                break;
+            }
+            change => println!("[task fs] saw {change:?}"),
+        }
+    }
+}
+
 fn main() {
     file_bits();
 }
@@ -0,0 +1,294 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+///upper bounds of each histogram bucket, in microseconds - anything larger ends up in the overflow bucket
+const BUCKET_BOUNDS_MICROS: [u64; 10] = [10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 1_000_000];
+
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKET_BOUNDS_MICROS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKET_BOUNDS_MICROS.len() + 1],
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = BUCKET_BOUNDS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MICROS.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let bound = BUCKET_BOUNDS_MICROS.get(i).copied().map(Duration::from_micros);
+                (bound, count.load(Ordering::Relaxed))
+            })
+            .collect();
+
+        HistogramSnapshot {
+            buckets,
+            sum: Duration::from_micros(self.sum_micros.load(Ordering::Relaxed)),
+            count: self.count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistogramSnapshot {
+    ///`(upper bound, count)` for each bucket, non-cumulative. The last bucket has no upper bound.
+    pub buckets: Vec<(Option<Duration>, u64)>,
+    pub sum: Duration,
+    pub count: u64,
+}
+
+impl HistogramSnapshot {
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).ok().filter(|c| *c > 0)?;
+        Some(self.sum / count)
+    }
+}
+
+#[derive(Default)]
+pub struct RunnerMetrics {
+    pub tasks: AtomicUsize,
+    pub spawned: AtomicU64,
+    pub completed: AtomicU64,
+    pub polls: AtomicU64,
+    pub wakes: AtomicU64,
+    pub poll_duration: Histogram,
+}
+
+impl RunnerMetrics {
+    pub fn snapshot(&self, index: usize) -> RunnerSnapshot {
+        RunnerSnapshot {
+            index,
+            tasks: self.tasks.load(Ordering::Relaxed),
+            spawned: self.spawned.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            polls: self.polls.load(Ordering::Relaxed),
+            wakes: self.wakes.load(Ordering::Relaxed),
+            poll_duration: self.poll_duration.snapshot(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RunnerSnapshot {
+    pub index: usize,
+    pub tasks: usize,
+    pub spawned: u64,
+    pub completed: u64,
+    pub polls: u64,
+    pub wakes: u64,
+    pub poll_duration: HistogramSnapshot,
+}
+
+pub struct IoMetrics {
+    pub queue_depth: AtomicUsize,
+    pub completed: AtomicU64,
+    pub latency: Histogram,
+}
+
+impl IoMetrics {
+    pub const fn new() -> Self {
+        Self {
+            queue_depth: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            latency: Histogram::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> IoSnapshot {
+        IoSnapshot {
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            latency: self.latency.snapshot(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IoSnapshot {
+    ///requests sent to the io thread which it hasn't started on yet
+    pub queue_depth: usize,
+    pub completed: u64,
+    ///time from a request being sent to its result being available
+    pub latency: HistogramSnapshot,
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    pub runners: Vec<RunnerSnapshot>,
+    pub timer_heap_size: usize,
+    pub io: IoSnapshot,
+}
+
+#[cfg(feature = "prometheus")]
+mod prometheus {
+    use super::{HistogramSnapshot, MetricsSnapshot, RunnerSnapshot};
+    use std::fmt::Write;
+
+    type RunnerMetric = (&'static str, &'static str, fn(&RunnerSnapshot) -> u64);
+
+    fn label_set(labels: &[&str]) -> String {
+        let labels: Vec<&str> = labels.iter().copied().filter(|l| !l.is_empty()).collect();
+        if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels.join(","))
+        }
+    }
+
+    fn write_histogram(out: &mut String, name: &str, labels: &str, histogram: &HistogramSnapshot) {
+        let mut cumulative = 0;
+        for (bound, count) in &histogram.buckets {
+            cumulative += count;
+            let le = bound.map_or_else(|| "+Inf".to_string(), |b| b.as_secs_f64().to_string());
+            let le = format!("le=\"{le}\"");
+            let _ = writeln!(out, "{name}_bucket{} {cumulative}", label_set(&[labels, &le]));
+        }
+        let _ = writeln!(out, "{name}_sum{} {}", label_set(&[labels]), histogram.sum.as_secs_f64());
+        let _ = writeln!(out, "{name}_count{} {}", label_set(&[labels]), histogram.count);
+    }
+
+    impl MetricsSnapshot {
+        ///renders the snapshot in the prometheus text exposition format
+        pub fn to_prometheus(&self) -> String {
+            let mut out = String::new();
+
+            let gauges_and_counters: [RunnerMetric; 5] = [
+                ("async_executor_runner_tasks", "gauge", |r| r.tasks as u64),
+                ("async_executor_runner_spawned_total", "counter", |r| r.spawned),
+                ("async_executor_runner_completed_total", "counter", |r| r.completed),
+                ("async_executor_runner_polls_total", "counter", |r| r.polls),
+                ("async_executor_runner_wakes_total", "counter", |r| r.wakes),
+            ];
+            for (name, kind, get) in gauges_and_counters {
+                let _ = writeln!(out, "# TYPE {name} {kind}");
+                for runner in &self.runners {
+                    let _ = writeln!(out, "{name}{{runner=\"{}\"}} {}", runner.index, get(runner));
+                }
+            }
+
+            let _ = writeln!(out, "# TYPE async_executor_runner_poll_duration_seconds histogram");
+            for runner in &self.runners {
+                write_histogram(
+                    &mut out,
+                    "async_executor_runner_poll_duration_seconds",
+                    &format!("runner=\"{}\"", runner.index),
+                    &runner.poll_duration,
+                );
+            }
+
+            let _ = writeln!(out, "# TYPE async_executor_timer_heap_size gauge");
+            let _ = writeln!(out, "async_executor_timer_heap_size {}", self.timer_heap_size);
+
+            let _ = writeln!(out, "# TYPE async_executor_io_queue_depth gauge");
+            let _ = writeln!(out, "async_executor_io_queue_depth {}", self.io.queue_depth);
+            let _ = writeln!(out, "# TYPE async_executor_io_completed_total counter");
+            let _ = writeln!(out, "async_executor_io_completed_total {}", self.io.completed);
+            let _ = writeln!(out, "# TYPE async_executor_io_latency_seconds histogram");
+            write_histogram(&mut out, "async_executor_io_latency_seconds", "", &self.io.latency);
+
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::Histogram;
+    #[cfg(feature = "prometheus")]
+    use crate::metrics::{IoMetrics, MetricsSnapshot, RunnerMetrics};
+    #[cfg(feature = "prometheus")]
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    #[test]
+    fn histogram_buckets() {
+        let histogram = Histogram::new();
+        histogram.record(Duration::from_micros(5));
+        histogram.record(Duration::from_micros(700));
+        histogram.record(Duration::from_secs(5));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 3);
+        assert_eq!(snapshot.buckets[0], (Some(Duration::from_micros(10)), 1));
+        assert_eq!(snapshot.buckets[4], (Some(Duration::from_millis(1)), 1));
+        assert_eq!(snapshot.buckets.last(), Some(&(None, 1)));
+        assert_eq!(snapshot.sum, Duration::from_micros(5_000_705));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus_format() {
+        let runner = RunnerMetrics::default();
+        runner.tasks.store(2, Ordering::Relaxed);
+        runner.polls.store(7, Ordering::Relaxed);
+        runner.poll_duration.record(Duration::from_micros(30));
+        let io = IoMetrics::new();
+        io.queue_depth.store(3, Ordering::Relaxed);
+        io.latency.record(Duration::from_millis(2));
+
+        let snapshot = MetricsSnapshot {
+            runners: vec![runner.snapshot(0), RunnerMetrics::default().snapshot(1)],
+            timer_heap_size: 4,
+            io: io.snapshot(),
+        };
+        let text = snapshot.to_prometheus();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"# TYPE async_executor_runner_tasks gauge"));
+        assert!(lines.contains(&"async_executor_runner_tasks{runner=\"0\"} 2"));
+        assert!(lines.contains(&"async_executor_runner_tasks{runner=\"1\"} 0"));
+        assert!(lines.contains(&"# TYPE async_executor_runner_polls_total counter"));
+        assert!(lines.contains(&"async_executor_runner_polls_total{runner=\"0\"} 7"));
+
+        //buckets are cumulative, with the runner label first
+        assert!(lines.contains(&"# TYPE async_executor_runner_poll_duration_seconds histogram"));
+        assert!(lines.contains(&"async_executor_runner_poll_duration_seconds_bucket{runner=\"0\",le=\"0.00001\"} 0"));
+        assert!(lines.contains(&"async_executor_runner_poll_duration_seconds_bucket{runner=\"0\",le=\"0.00005\"} 1"));
+        assert!(lines.contains(&"async_executor_runner_poll_duration_seconds_bucket{runner=\"0\",le=\"+Inf\"} 1"));
+        assert!(lines.contains(&"async_executor_runner_poll_duration_seconds_count{runner=\"0\"} 1"));
+
+        assert!(lines.contains(&"async_executor_timer_heap_size 4"));
+        assert!(lines.contains(&"# TYPE async_executor_io_queue_depth gauge"));
+        assert!(lines.contains(&"async_executor_io_queue_depth 3"));
+        //unlabelled histograms don't get an empty `{}`
+        assert!(lines.contains(&"async_executor_io_latency_seconds_bucket{le=\"0.005\"} 1"));
+        assert!(lines.contains(&"async_executor_io_latency_seconds_count 1"));
+
+        //every sample comes after its metric's TYPE line
+        for line in lines.iter().filter(|line| !line.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().unwrap();
+            let family = ["_bucket", "_sum", "_count"].iter()
+                .find_map(|suffix| name.strip_suffix(suffix))
+                .filter(|family| text.contains(&format!("# TYPE {family} histogram")))
+                .unwrap_or(name);
+            assert!(text.contains(&format!("# TYPE {family} ")), "{line} has no TYPE");
+        }
+    }
+}
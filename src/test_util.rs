@@ -0,0 +1,44 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+///a path in the temp directory for a test to use, which is cleared out beforehand and removed afterwards - even if
+///the test panics partway through
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    ///`name` only has to be unique between tests, as the process id is added to keep separate runs apart
+    pub fn new (name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("async_executor_{name}_{}", std::process::id()));
+        remove(&path);
+        Self(path)
+    }
+}
+
+///removes whatever is at `path`, if anything
+fn remove (path: &Path) {
+    let _ = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(_) => return,
+    };
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        remove(&self.0);
+    }
+}
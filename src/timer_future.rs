@@ -3,9 +3,18 @@ use std::collections::BinaryHeap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::LazyLock;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::mpsc::{channel, Sender};
 use std::task::{Context, Poll, Waker};
 use std::time::{Duration, Instant};
+use crate::trace;
+
+static TIMER_HEAP_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+///the number of timers which are currently waiting to fire
+pub fn timer_heap_size() -> usize {
+    TIMER_HEAP_SIZE.load(AtomicOrdering::Relaxed)
+}
 
 struct TimerThread {
     requests_tx: Sender<WakerAndEnd>,
@@ -32,10 +41,7 @@ impl PartialEq for WakerAndEnd {
 
 impl PartialOrd<Self> for WakerAndEnd {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let s = Reverse(self.end);
-        let o = Reverse(other.end);
-
-        s.partial_cmp(&o)
+        Some(self.cmp(other))
     }
 }
 
@@ -51,18 +57,24 @@ impl Ord for WakerAndEnd {
 impl TimerThread {
     pub fn get () -> &'static Self {
         static INSTANCE: LazyLock<TimerThread> = LazyLock::new(|| {
-            let (requests_tx, requests_rx) = channel();
+            let (requests_tx, requests_rx) = channel::<WakerAndEnd>();
             std::thread::Builder::new()
                 .name("timer_thread".into())
                 .spawn(move || {
                     let mut to_check = BinaryHeap::new();
 
                     loop {
-                        to_check.extend(requests_rx.try_iter());
+                        for wae in requests_rx.try_iter() {
+                            trace!("timer", "registered timer due in {:?}", wae.end.saturating_duration_since(Instant::now()));
+                            to_check.push(wae);
+                        }
+                        TIMER_HEAP_SIZE.store(to_check.len(), AtomicOrdering::Relaxed);
                         
                         let top_element_finished = to_check.peek().is_some_and(|wae: &WakerAndEnd| wae.is_finished());
                         if top_element_finished {
                             let top_element = to_check.pop().unwrap();
+                            TIMER_HEAP_SIZE.store(to_check.len(), AtomicOrdering::Relaxed);
+                            trace!("timer", "timer fired {:?} late", top_element.end.elapsed());
                             top_element.waker.wake();
                         } else {
                             std::thread::yield_now();